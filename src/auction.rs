@@ -0,0 +1,397 @@
+use std::cmp::Ordering;
+
+use cosmwasm_std::{to_binary, Addr, Binary, Deps, DepsMut, Env, Order, Response, StdError, StdResult, Uint128};
+use cw20::Balance;
+use cw_storage_plus::Bound;
+use cw_utils::{Duration, Expiration};
+use sha2::{Digest, Sha256};
+
+use crate::compliance::{check_allowed_addresses, check_allowed_assets};
+use crate::contract::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::msg::{GetAuctionsResponse, GetSealedBidsResponse, NewAuction, NewAuctionResponse};
+use crate::state::{AuctionInfo, Bid, SealedBid, AUCTIONS, SEALED_BIDS, STATE};
+use crate::utils::{send_asset_msg, single_asset, validate_denom};
+
+// upper bound for the deposits walked through on settlement
+const MAX_SEALED_BIDS: usize = 50;
+
+
+
+pub fn try_create_auction(
+    deps: DepsMut,
+    env: Env,
+    seller: &Addr,
+    sell_balance: Balance,
+    msg: NewAuction,
+) -> Result<Response, ContractError> {
+
+    let mut config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "The factory has been stopped.  No new auction can be created".to_string()
+            }
+        ));
+    }
+
+    let invalid = |reason: &str| Err(ContractError::InvalidAuction { reason: reason.to_string() });
+
+    match (msg.end, msg.extension) {
+        (Expiration::Never {}, _) => return invalid("auction must have an end"),
+        (Expiration::AtHeight(_), Some(Duration::Time(_))) |
+        (Expiration::AtTime(_), Some(Duration::Height(_))) => {
+            return invalid("extension must be of the same kind as the end")
+        },
+        _ => {}
+    }
+
+    match &msg.sealed {
+        Some(sealed) => {
+            if msg.extension.is_some() { return invalid("sealed auctions can't be extended"); }
+            if sealed.reveal_end.partial_cmp(&msg.end) != Some(Ordering::Greater) {
+                return invalid("reveal end must be after the end");
+            }
+        },
+        None => {
+            if msg.min_increment.is_zero() { return invalid("min increment must be positive"); }
+        }
+    }
+
+    if msg.end.is_expired(&env.block) { return Err(ContractError::Expired {}); }
+
+    let bid_asset = validate_denom(deps.api, msg.bid_asset)?;
+
+    let (sell_asset, sell_amount) = single_asset(sell_balance)?;
+
+    check_allowed_addresses(deps.storage, [seller])?;
+    check_allowed_assets(deps.storage, [&sell_asset, &bid_asset])?;
+
+    let auction = AuctionInfo {
+        seller: seller.clone(),
+        sell_asset,
+        sell_amount,
+        bid_asset,
+        reserve_price: msg.reserve_price,
+        min_increment: msg.min_increment,
+        end: msg.end,
+        extension: msg.extension,
+        highest_bid: None,
+        user_info: msg.user_info,
+        description: msg.description,
+        sealed: msg.sealed,
+    };
+
+    let auction_id = config.auction_index;
+
+    AUCTIONS.save(deps.storage, auction_id, &auction)?;
+    config.auction_index += 1;
+    STATE.save(deps.storage, &config)?;
+
+    let data = NewAuctionResponse {
+        id: auction_id,
+        auction
+    };
+
+    Ok(Response::new()
+        .set_data(to_binary(&data)?)
+        .add_attribute("method", "create_auction")
+    )
+}
+
+
+
+pub fn try_bid(
+    deps: DepsMut,
+    env: Env,
+    bidder: &Addr,
+    auction_id: u32,
+    balance: Balance,
+) -> Result<Response, ContractError> {
+
+    let mut auction = AUCTIONS.load(deps.storage, auction_id)?;
+
+    if auction.sealed.is_some() { return Err(ContractError::SealedAuction {}); }
+
+    if auction.end.is_expired(&env.block) { return Err(ContractError::AuctionEnded {}); }
+
+    if auction.seller == *bidder {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "Can't bid on your own auction".to_string()
+            }
+        ));
+    }
+
+    let (asset, amount) = single_asset(balance)?;
+
+    if asset != auction.bid_asset { return Err(ContractError::WrongDenom {}); }
+
+    check_allowed_addresses(deps.storage, [bidder, &auction.seller])?;
+    check_allowed_assets(deps.storage, [&auction.sell_asset, &auction.bid_asset])?;
+
+    let min = match &auction.highest_bid {
+        Some(bid) => bid.amount + auction.min_increment,
+        None => Uint128::new(1)
+    };
+
+    if amount < min { return Err(ContractError::BidTooLow { min }); }
+
+    let mut res = Response::new()
+        .add_attribute("method", "bid")
+        .add_attribute("amount", amount);
+
+    // outbid bidder gets the escrow back right away
+    if let Some(previous) = auction.highest_bid.replace(Bid { bidder: bidder.clone(), amount }) {
+        res = res.add_message(send_asset_msg(&auction.bid_asset, previous.amount, previous.bidder.as_str())?);
+    }
+
+    if let Some(extension) = auction.extension {
+        let extended = extension.after(&env.block);
+        if extended > auction.end {
+            auction.end = extended;
+            res = res.add_attribute("extended_to", extended.to_string());
+        }
+    }
+
+    AUCTIONS.save(deps.storage, auction_id, &auction)?;
+
+    Ok(res)
+}
+
+
+
+pub fn try_commit_bid(
+    deps: DepsMut,
+    env: Env,
+    bidder: &Addr,
+    auction_id: u32,
+    commitment: Binary,
+    balance: Balance,
+) -> Result<Response, ContractError> {
+
+    let auction = AUCTIONS.load(deps.storage, auction_id)?;
+
+    if auction.sealed.is_none() { return Err(ContractError::NotSealedAuction {}); }
+
+    if auction.end.is_expired(&env.block) { return Err(ContractError::AuctionEnded {}); }
+
+    if auction.seller == *bidder {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "Can't bid on your own auction".to_string()
+            }
+        ));
+    }
+
+    if commitment.len() != 32 { return Err(ContractError::InvalidCommitment {}); }
+
+    if SEALED_BIDS.has(deps.storage, (auction_id, bidder)) {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "Bid has already been committed".to_string()
+            }
+        ));
+    }
+
+    let committed = SEALED_BIDS
+        .prefix(auction_id)
+        .keys_raw(deps.storage, None, None, Order::Ascending)
+        .take(MAX_SEALED_BIDS)
+        .count();
+
+    if committed >= MAX_SEALED_BIDS { return Err(ContractError::TooManyBids {}); }
+
+    let (asset, deposit) = single_asset(balance)?;
+
+    if asset != auction.bid_asset { return Err(ContractError::WrongDenom {}); }
+
+    check_allowed_addresses(deps.storage, [bidder, &auction.seller])?;
+    check_allowed_assets(deps.storage, [&auction.sell_asset, &auction.bid_asset])?;
+
+    // keeps the limited commitment slots from being taken by dust deposits
+    if deposit < auction.reserve_price { return Err(ContractError::BidTooLow { min: auction.reserve_price }); }
+
+    SEALED_BIDS.save(deps.storage, (auction_id, bidder), &SealedBid { commitment, deposit })?;
+
+    Ok(Response::new()
+        .add_attribute("method", "commit_bid")
+        .add_attribute("deposit", deposit)
+    )
+}
+
+
+
+pub fn try_reveal_bid(
+    deps: DepsMut,
+    env: Env,
+    bidder: &Addr,
+    auction_id: u32,
+    amount: Uint128,
+    salt: Binary,
+) -> Result<Response, ContractError> {
+
+    let mut auction = AUCTIONS.load(deps.storage, auction_id)?;
+
+    let sealed = auction.sealed.as_ref().ok_or(ContractError::NotSealedAuction {})?;
+
+    if !auction.end.is_expired(&env.block) || sealed.reveal_end.is_expired(&env.block) {
+        return Err(ContractError::NotRevealPhase {});
+    }
+
+    let bid = SEALED_BIDS.load(deps.storage, (auction_id, bidder))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(amount.u128().to_be_bytes());
+    hasher.update(salt.as_slice());
+
+    if hasher.finalize().as_slice() != bid.commitment.as_slice() || amount > bid.deposit || amount.is_zero() {
+        return Err(ContractError::InvalidCommitment {});
+    }
+
+    SEALED_BIDS.remove(deps.storage, (auction_id, bidder));
+
+    let mut res = Response::new()
+        .add_attribute("method", "reveal_bid")
+        .add_attribute("amount", amount);
+
+    let leading = match &auction.highest_bid {
+        Some(highest) => amount > highest.amount,
+        None => true
+    };
+
+    // everything that can't win anymore is refunded right away
+    let refund = if leading {
+        if let Some(previous) = auction.highest_bid.replace(Bid { bidder: bidder.clone(), amount }) {
+            res = res.add_message(send_asset_msg(&auction.bid_asset, previous.amount, previous.bidder.as_str())?);
+        }
+        bid.deposit - amount
+    } else {
+        bid.deposit
+    };
+
+    if !refund.is_zero() {
+        res = res.add_message(send_asset_msg(&auction.bid_asset, refund, bidder.as_str())?);
+    }
+
+    AUCTIONS.save(deps.storage, auction_id, &auction)?;
+
+    Ok(res)
+}
+
+
+
+pub fn try_settle(
+    deps: DepsMut,
+    env: Env,
+    auction_id: u32,
+) -> Result<Response, ContractError> {
+
+    let auction = AUCTIONS.load(deps.storage, auction_id)?;
+
+    let ends = match &auction.sealed {
+        Some(sealed) => sealed.reveal_end,
+        None => auction.end
+    };
+
+    if !ends.is_expired(&env.block) { return Err(ContractError::AuctionNotEnded {}); }
+
+    AUCTIONS.remove(deps.storage, auction_id);
+
+    let seller = auction.seller.as_str();
+
+    let mut res = Response::new();
+
+    if let Some(sealed) = &auction.sealed {
+        let unrevealed = SEALED_BIDS
+            .prefix(auction_id)
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        for (bidder, bid) in unrevealed {
+            let recipient = if sealed.slash_unrevealed { seller } else { bidder.as_str() };
+            res = res.add_message(send_asset_msg(&auction.bid_asset, bid.deposit, recipient)?);
+            SEALED_BIDS.remove(deps.storage, (auction_id, &bidder));
+        }
+    }
+
+    let res = match auction.highest_bid {
+        Some(bid) if bid.amount >= auction.reserve_price => {
+            res
+                .add_message(send_asset_msg(&auction.bid_asset, bid.amount, seller)?)
+                .add_message(send_asset_msg(&auction.sell_asset, auction.sell_amount, bid.bidder.as_str())?)
+                .add_attribute("winner", bid.bidder)
+                .add_attribute("amount", bid.amount)
+        },
+        highest_bid => {
+            let mut res = res
+                .add_message(send_asset_msg(&auction.sell_asset, auction.sell_amount, seller)?)
+                .add_attribute("reserve_met", "false");
+
+            if let Some(bid) = highest_bid {
+                res = res.add_message(send_asset_msg(&auction.bid_asset, bid.amount, bid.bidder.as_str())?);
+            }
+            res
+        }
+    };
+
+    Ok(res.add_attribute("method", "settle_auction"))
+}
+
+
+
+pub fn query_auctions(
+    deps: Deps,
+    env: Env,
+    include_ended: bool,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<GetAuctionsResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let auctions = AUCTIONS
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending
+        )
+        .filter(|auction| match auction {
+            Ok((_, auction)) => include_ended || !auction.end.is_expired(&env.block),
+            Err(_) => true
+        })
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetAuctionsResponse { auctions })
+}
+
+
+
+pub fn query_sealed_bids(
+    deps: Deps,
+    auction_id: u32,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<GetSealedBidsResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+
+    let bids = SEALED_BIDS
+        .prefix(auction_id)
+        .range(
+            deps.storage,
+            start_after.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending
+        )
+        .take(limit)
+        .map(|bid| bid.map(|(bidder, bid)| (bidder.into_string(), bid)))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetSealedBidsResponse { bids })
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{coins, from_binary, to_binary, Addr, BankMsg, Coin, DepsMut, Env, Response, Uint128};
+use cw20::{Balance, Denom};
+
+use crate::contract::{try_cancel, try_create_otc, try_swap, SwapOptions};
+use crate::error::ContractError;
+use crate::msg::{BatchItemResult, BatchNewOTC, BatchResponse, NewOTCResponse};
+use crate::listing::split_deposit;
+use crate::oracle::ask_amount;
+use crate::state::OTCS;
+
+
+
+/// Sums up coins by denom
+fn by_denom<'a>(coins: impl Iterator<Item = &'a Coin>) -> BTreeMap<String, Uint128> {
+    let mut totals: BTreeMap<String, Uint128> = BTreeMap::new();
+    for coin in coins {
+        *totals.entry(coin.denom.clone()).or_default() += coin.amount;
+    }
+    totals
+}
+
+
+/// Carries the messages and events of a single item over to the batch response
+pub(crate) fn merge(res: Response, item: Response) -> Response {
+    res.add_submessages(item.messages).add_events(item.events)
+}
+
+
+
+pub fn try_batch_create(
+    mut deps: DepsMut,
+    env: Env,
+    seller: &Addr,
+    funds: Vec<Coin>,
+    otcs: Vec<BatchNewOTC>,
+) -> Result<Response, ContractError> {
+
+    if otcs.iter().any(|otc| otc.sell.amount.is_zero()) { return Err(ContractError::NoFunds {}); }
+
+    let (funds, deposit) = split_deposit(deps.storage, funds, otcs.len())?;
+
+    if by_denom(otcs.iter().map(|otc| &otc.sell)) != by_denom(funds.iter()) {
+        return Err(ContractError::FundsMismatch {});
+    }
+
+    let mut res = Response::new().add_attribute("method", "batch_create");
+    let mut results = vec!();
+
+    for BatchNewOTC { sell, otc } in otcs {
+        let created = try_create_otc(
+            deps.branch(),
+            env.clone(),
+            seller,
+            Balance::from(vec!(sell)),
+            deposit.clone(),
+            otc
+        )?;
+
+        let data: NewOTCResponse = from_binary(created.data.as_ref().unwrap())?;
+
+        results.push(BatchItemResult {
+            otc_id: data.id,
+            sell_amount: data.otc.sell_amount,
+            ask_amount: data.otc.ask_amount,
+        });
+
+        res = merge(res, created);
+    }
+
+    Ok(res.set_data(to_binary(&BatchResponse { results })?))
+}
+
+
+
+pub fn try_batch_swap(
+    mut deps: DepsMut,
+    env: Env,
+    payer: &Addr,
+    funds: Vec<Coin>,
+    otc_ids: Vec<u32>,
+) -> Result<Response, ContractError> {
+
+    let mut payments = vec!();
+    let mut results = vec!();
+
+    for otc_id in otc_ids.iter() {
+        let otc = OTCS.load(deps.storage, *otc_id)?;
+
+        let denom = match otc.ask_asset() {
+            Denom::Native(denom) => denom,
+            Denom::Cw20(_) => return Err(ContractError::WrongDenom {})
+        };
+
+        let ask_amount = ask_amount(&deps.querier, &env, &otc)?;
+
+        payments.push(Coin { denom, amount: ask_amount });
+        results.push(BatchItemResult {
+            otc_id: *otc_id,
+            sell_amount: otc.sell_amount,
+            ask_amount,
+        });
+    }
+
+    let mut left = by_denom(funds.iter());
+
+    for (denom, needed) in by_denom(payments.iter()) {
+        let sent = left.entry(denom).or_default();
+        *sent = sent.checked_sub(needed).map_err(|_| ContractError::FundsMismatch {})?;
+    }
+
+    let mut res = Response::new().add_attribute("method", "batch_swap");
+
+    for (otc_id, payment) in otc_ids.into_iter().zip(payments) {
+        let swapped = try_swap(
+            deps.branch(),
+            env.clone(),
+            payer,
+            otc_id,
+            Balance::from(vec!(payment)),
+            SwapOptions::default()
+        )?;
+        res = merge(res, swapped);
+    }
+
+    let refund: Vec<Coin> = left
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .flat_map(|(denom, amount)| coins(amount.u128(), denom))
+        .collect();
+
+    if !refund.is_empty() {
+        res = res.add_message(BankMsg::Send { to_address: payer.to_string(), amount: refund });
+    }
+
+    Ok(res.set_data(to_binary(&BatchResponse { results })?))
+}
+
+
+
+pub fn try_batch_cancel(
+    mut deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    otc_ids: Vec<u32>,
+) -> Result<Response, ContractError> {
+
+    let mut res = Response::new().add_attribute("method", "batch_cancel");
+    let mut results = vec!();
+
+    for otc_id in otc_ids {
+        let otc = OTCS.load(deps.storage, otc_id)?;
+
+        results.push(BatchItemResult {
+            otc_id,
+            sell_amount: otc.escrowed(),
+            ask_amount: otc.ask_amount,
+        });
+
+        res = merge(res, try_cancel(deps.branch(), env.clone(), sender, otc_id)?);
+    }
+
+    Ok(res.set_data(to_binary(&BatchResponse { results })?))
+}
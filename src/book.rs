@@ -0,0 +1,173 @@
+use cosmwasm_std::{Decimal256, Deps, Env, Order, StdResult};
+use cw20::Denom;
+use cw_storage_plus::Bound;
+
+use crate::contract::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::msg::{BestOfferResponse, GetOTCsResponse, PairBookResponse, PairInfo, PairsResponse, PriceLevel};
+use crate::state::{pair_key, price_key, OTCInfo, OTCS, PAIRS, PRICE_INDEX};
+use crate::utils::validate_denom;
+
+
+
+/// Open fixed price otcs of a pair from the price index grouped in price levels,
+/// decaying and pegged otcs aren't kept in the index
+pub fn query_pair_book(
+    deps: Deps,
+    env: Env,
+    sell_asset: Denom,
+    ask_asset: Denom,
+    start_after: Option<(Decimal256, u32)>,
+    limit: Option<u32>,
+) -> StdResult<PairBookResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let pair = pair_key(
+        &validate_denom(deps.api, sell_asset)?, 
+        &validate_denom(deps.api, ask_asset)?
+    );
+
+    let start_key = start_after.map(|(price, id)| (price_key(price), id));
+    let start = start_key.as_ref().map(|(price, id)| Bound::exclusive((price.as_slice(), *id)));
+
+    let otcs = walk_index(deps, &env, &pair, start, None, limit)?;
+
+    let mut levels: Vec<PriceLevel> = vec!();
+
+    for (_, otc) in otcs.iter() {
+        let price = otc.current_price(&env.block);
+
+        match levels.last_mut() {
+            Some(level) if level.price == price => {
+                level.sell_amount += otc.sell_amount;
+                level.ask_amount += otc.ask_amount;
+                level.count += 1;
+            },
+            _ => {
+                levels.push(PriceLevel { 
+                    price, 
+                    sell_amount: otc.sell_amount, 
+                    ask_amount: otc.ask_amount, 
+                    count: 1 
+                });
+            }
+        }
+    }
+
+    Ok(PairBookResponse { otcs, levels })
+}
+
+
+
+pub fn query_pairs(
+    deps: Deps,
+    start_after: Option<(Denom, Denom)>,
+    limit: Option<u32>,
+) -> StdResult<PairsResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let start_key = start_after.map(|(sell_asset, ask_asset)| pair_key(&sell_asset, &ask_asset));
+
+    let pairs = PAIRS
+        .range(deps.storage, start_key.as_deref().map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .map(|pair| pair.map(|(_, pair)| PairInfo { 
+            sell_asset: pair.sell_asset, 
+            ask_asset: pair.ask_asset, 
+            count: pair.open 
+        }))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(PairsResponse { pairs })
+}
+
+
+
+/// Walks the price index of a pair in price order skipping expired otcs, stops after
+/// `MAX_LIMIT` skipped entries as expired otcs stay indexed until `CleanupExpired` removes them
+fn walk_index<'a>(
+    deps: Deps,
+    env: &Env,
+    pair: &str,
+    min: Option<Bound<'a, (&'a [u8], u32)>>,
+    max: Option<Bound<'a, (&'a [u8], u32)>>,
+    limit: usize,
+) -> StdResult<Vec<(u32, OTCInfo)>> {
+
+    let mut offers = vec!();
+    let mut skipped = 0;
+
+    for key in PRICE_INDEX.sub_prefix(pair).keys(deps.storage, min, max, Order::Ascending) {
+        let (_, id) = key?;
+        let otc = OTCS.load(deps.storage, id)?;
+
+        if otc.expires.is_expired(&env.block) {
+            skipped += 1;
+            if skipped == MAX_LIMIT { break; }
+            continue;
+        }
+
+        offers.push((id, otc));
+        if offers.len() == limit { break; }
+    }
+
+    Ok(offers)
+}
+
+
+
+/// Walks the price index of a pair from `min_price` up to `max_price` skipping expired otcs
+pub(crate) fn offers_by_price(
+    deps: Deps,
+    env: &Env,
+    sell_asset: Denom,
+    ask_asset: Denom,
+    min_price: Option<Decimal256>,
+    max_price: Option<Decimal256>,
+    limit: usize,
+) -> StdResult<Vec<(u32, OTCInfo)>> {
+
+    let pair = pair_key(
+        &validate_denom(deps.api, sell_asset)?, 
+        &validate_denom(deps.api, ask_asset)?
+    );
+
+    let (min_key, max_key) = (min_price.map(price_key), max_price.map(price_key));
+
+    let min = min_key.as_ref().map(|price| Bound::inclusive((price.as_slice(), 0)));
+    let max = max_key.as_ref().map(|price| Bound::inclusive((price.as_slice(), u32::MAX)));
+
+    walk_index(deps, env, &pair, min, max, limit)
+}
+
+
+
+pub fn query_best_offer(
+    deps: Deps,
+    env: Env,
+    sell_asset: Denom,
+    ask_asset: Denom,
+) -> StdResult<BestOfferResponse> {
+    let offer = offers_by_price(deps, &env, sell_asset, ask_asset, None, None, 1)?.pop();
+    Ok(BestOfferResponse { offer })
+}
+
+
+
+pub fn query_offers_by_price(
+    deps: Deps,
+    env: Env,
+    sell_asset: Denom,
+    ask_asset: Denom,
+    min_price: Option<Decimal256>,
+    max_price: Option<Decimal256>,
+    limit: Option<u32>,
+) -> StdResult<GetOTCsResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    Ok(GetOTCsResponse { 
+        otcs: offers_by_price(deps, &env, sell_asset, ask_asset, min_price, max_price, limit)? 
+    })
+}
@@ -0,0 +1,160 @@
+use cosmwasm_std::{Addr, Deps, DepsMut, Empty, Order, Response, StdResult, Storage};
+use cw20::Denom;
+use cw_storage_plus::Bound;
+
+use crate::contract::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::hooks::ensure_admin;
+use crate::msg::{AllowListResponse, DenyListResponse};
+use crate::state::{ALLOWED_ASSETS, DENIED_ADDRESSES, STATE};
+use crate::utils::{denom_key, validate_denom};
+
+
+
+/// Admin only, adds and removes denied addresses in a single batch
+pub fn try_update_deny_list(
+    deps: DepsMut,
+    sender: &Addr,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(&deps, sender)?;
+
+    for address in add.iter() {
+        DENIED_ADDRESSES.save(deps.storage, &deps.api.addr_validate(address)?, &Empty {})?;
+    }
+    for address in remove.iter() {
+        DENIED_ADDRESSES.remove(deps.storage, &deps.api.addr_validate(address)?);
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_deny_list")
+        .add_attribute("added", add.len().to_string())
+        .add_attribute("removed", remove.len().to_string())
+    )
+}
+
+
+
+/// Admin only, adds and removes allowed assets in a single batch,
+/// the list is only enforced once `enforce` has been set
+pub fn try_update_allow_list(
+    deps: DepsMut,
+    sender: &Addr,
+    add: Vec<Denom>,
+    remove: Vec<Denom>,
+    enforce: Option<bool>,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(&deps, sender)?;
+
+    let (added, removed) = (add.len(), remove.len());
+
+    for asset in add {
+        let asset = validate_denom(deps.api, asset)?;
+        ALLOWED_ASSETS.save(deps.storage, &denom_key(&asset), &asset)?;
+    }
+    for asset in remove {
+        ALLOWED_ASSETS.remove(deps.storage, &denom_key(&asset));
+    }
+
+    if let Some(enforce) = enforce {
+        let mut config = STATE.load(deps.storage)?;
+        config.restrict_assets = enforce;
+        STATE.save(deps.storage, &config)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_allow_list")
+        .add_attribute("added", added.to_string())
+        .add_attribute("removed", removed.to_string())
+    )
+}
+
+
+
+/// Fails if any of the addresses is denied
+pub fn check_allowed_addresses<'a>(
+    storage: &dyn Storage,
+    addresses: impl IntoIterator<Item = &'a Addr>,
+) -> Result<(), ContractError> {
+    for address in addresses {
+        if DENIED_ADDRESSES.has(storage, address) {
+            return Err(ContractError::AddressDenied { address: address.to_string() });
+        }
+    }
+    Ok(())
+}
+
+
+
+/// Fails if the allow-list is enforced and misses any of the assets
+pub fn check_allowed_assets<'a>(
+    storage: &dyn Storage,
+    assets: impl IntoIterator<Item = &'a Denom>,
+) -> Result<(), ContractError> {
+    if !STATE.load(storage)?.restrict_assets { return Ok(()); }
+
+    for asset in assets {
+        let key = denom_key(asset);
+        if !ALLOWED_ASSETS.has(storage, &key) {
+            return Err(ContractError::AssetNotAllowed { asset: key });
+        }
+    }
+    Ok(())
+}
+
+
+
+pub fn query_deny_list(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<DenyListResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let start_after = start_after.map(|address| deps.api.addr_validate(&address)).transpose()?;
+
+    let addresses = DENIED_ADDRESSES
+        .keys(
+            deps.storage,
+            start_after.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(DenyListResponse { addresses })
+}
+
+
+
+pub fn query_allow_list(
+    deps: Deps,
+    start_after: Option<Denom>,
+    limit: Option<u32>,
+) -> StdResult<AllowListResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let start_after = start_after.map(|asset| denom_key(&asset));
+
+    let assets = ALLOWED_ASSETS
+        .range(
+            deps.storage,
+            start_after.as_deref().map(Bound::exclusive),
+            None,
+            Order::Ascending
+        )
+        .take(limit)
+        .map(|asset| asset.map(|(_, asset)| asset))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(AllowListResponse { 
+        enforced: STATE.load(deps.storage)?.restrict_assets, 
+        assets 
+    })
+}
@@ -1,44 +1,49 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Uint128, Addr, WasmMsg, from_binary, BankMsg, CosmosMsg, Coin, Order,
+    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdError, StdResult, Uint128, Addr, Api, Decimal, from_binary, CosmosMsg, Coin, Order,
 };
 use cw2::set_contract_version;
 
-use cw20::{Balance, Cw20ReceiveMsg, Cw20CoinVerified, Cw20ExecuteMsg};
+use cw20::{Balance, Cw20ReceiveMsg, Cw20CoinVerified, Denom};
+use cw_utils::NativeBalance;
 use cw_storage_plus::Bound;
-use cw_utils::Expiration;
+use cw_utils::Scheduled;
 
+use crate::auction::{try_create_auction, try_bid, try_commit_bid, try_reveal_bid, try_settle, query_auctions, query_sealed_bids};
+use crate::batch::{try_batch_create, try_batch_swap, try_batch_cancel};
+use crate::book::{query_pair_book, query_pairs, query_best_offer, query_offers_by_price};
+use crate::compliance::{check_allowed_addresses, check_allowed_assets, try_update_allow_list, try_update_deny_list, query_allow_list, query_deny_list};
+use crate::dca::{try_create_program, try_cancel_program, try_crank, query_programs};
 use crate::error::ContractError;
-use crate::state::{State, STATE, OTCS, OTCInfo, UserInfo};
-use crate::msg::{InstantiateMsg, QueryMsg, ExecuteMsg, ReceiveMsg, GetOTCsResponse, NewOTCResponse};
+use crate::escrow::{open_deal, try_confirm_deal, try_release_deal, try_dispute_deal, try_resolve_deal, query_deals};
+use crate::gating::{check_counterparty, try_set_member_group};
+use crate::events::{otc_closed, otc_created, otc_filled, Fill};
+use crate::hooks::{hook_msgs, hook_reply, try_add_hook, try_remove_hook, HOOK_REPLY_ID};
+use crate::utils::{send_asset_msg, payout_msg, single_asset};
+use crate::listing::{check_listing, deposit_msg, split_deposit, take_credit, try_update_listing_rules, try_top_up_credit, try_withdraw_credit, try_cleanup_expired, query_listing_rules, query_listing_credit, query_expired_count};
+use crate::matching::{try_match, try_batch_match};
+use crate::nft::{instantiate_tokens, query_nft, try_nft, Cw721Msg, Cw721QueryMsg};
+use crate::vesting::{open_position, try_claim_vested, validate_vesting, vesting_start, query_position, query_positions};
+use crate::oracle::{ask_amount, check_condition, new_pegged_price, validate_condition};
+use crate::referral::{query_referral_earnings, record_referral, referral_fee, validate_referral_fee};
+use crate::rfq::{try_create_rfq, try_cancel_rfq, try_quote, try_withdraw_quote, try_accept_quote, query_rfqs, query_quotes};
+use crate::state::{State, STATE, OTCS, HOOKS, DEALS, PROGRAMS, OTCInfo, PayoutShare, TOTAL_BPS, DutchAuction, Deal, Escrow, Position, AUCTIONS, PriceImprovement, Asset, save_otc, remove_otc};
+use crate::msg::{InstantiateMsg, QueryMsg, ExecuteMsg, ReceiveMsg, GetOTCsResponse, NewOTCResponse, NewOTC, CurrentPriceResponse, SimulateSwapResponse, OtcHookMsg, FillableResponse};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:otc";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-const DEFAULT_LIMIT: u32 = 20;
-const MAX_LIMIT: u32 = 60;
-
-macro_rules! cast {
-    ($target: expr, $pat: path) => {
-        {
-            if let $pat(a) = $target { // #1
-                a
-            } else {
-                panic!(
-                    "mismatch variant when cast to {}", 
-                    stringify!($pat)); // #2
-            }
-        }
-    };
-}
+pub(crate) const DEFAULT_LIMIT: u32 = 20;
+pub(crate) const MAX_LIMIT: u32 = 60;
+
 
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    deps: DepsMut,
-    _env: Env,
+    mut deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     _msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
@@ -48,11 +53,24 @@ pub fn instantiate(
     let state = State { 
         active: true,
         index: 0,
+        auction_index: 0,
+        rfq_index: 0,
+        price_improvement: PriceImprovement::Taker,
+        referral_fee: Decimal::zero(),
+        deal_index: 0,
+        position_index: 0,
+        program_index: 0,
+        member_group: None,
+        restrict_assets: false,
+        listing_deposit: None,
+        max_open_otcs: None,
         admin: deps.api.addr_canonicalize(info.sender.as_str())?
     };
 
     STATE.save(deps.storage, &state)?;
 
+    instantiate_tokens(deps.branch(), env, info.clone())?;
+
     Ok(Response::new()
         .add_attribute("method", "instantiate")
         .add_attribute("owner", info.sender))
@@ -70,23 +88,310 @@ pub fn execute(
 ) -> Result<Response, ContractError> {
     
     match msg {
-        ExecuteMsg::Create(msg) => try_create_otc(
+        ExecuteMsg::Create(msg) => {
+            let (funds, deposit) = split_deposit(deps.storage, info.funds, 1)?;
+            try_create_otc(
+                deps,
+                env,
+                &info.sender,
+                Balance::from(funds), 
+                deposit,
+                msg
+            )
+        },
+
+        ExecuteMsg::Swap { otc_id, recipient, payout_msg, referrer } => try_swap(
+            deps, 
+            env,
+            &info.sender, 
+            otc_id,
+            Balance::from(info.funds),
+            SwapOptions { recipient, payout_msg, referrer }
+        ),
+
+        ExecuteMsg::Cancel { otc_id } => try_cancel(
             deps,
             env,
             &info.sender,
-            Balance::from(info.funds), 
-            msg.ask_balance,    
-            msg.expires,
-            msg.user_info,
-            msg.description
+            otc_id
         ),
 
-        ExecuteMsg::Swap { otc_id } => try_swap(
-            deps, 
-            &info.sender, 
-            otc_id,
+        ExecuteMsg::BatchCreate { otcs } => try_batch_create(
+            deps,
+            env,
+            &info.sender,
+            info.funds,
+            otcs
+        ),
+
+        ExecuteMsg::BatchSwap { otc_ids } => try_batch_swap(
+            deps,
+            env,
+            &info.sender,
+            info.funds,
+            otc_ids
+        ),
+
+        ExecuteMsg::BatchCancel { otc_ids } => try_batch_cancel(
+            deps,
+            env,
+            &info.sender,
+            otc_ids
+        ),
+
+        ExecuteMsg::CreateAuction(msg) => try_create_auction(
+            deps,
+            env,
+            &info.sender,
             Balance::from(info.funds),
-            true
+            msg
+        ),
+
+        ExecuteMsg::Bid { auction_id } => try_bid(
+            deps,
+            env,
+            &info.sender,
+            auction_id,
+            Balance::from(info.funds)
+        ),
+
+        ExecuteMsg::CommitBid { auction_id, commitment } => try_commit_bid(
+            deps,
+            env,
+            &info.sender,
+            auction_id,
+            commitment,
+            Balance::from(info.funds)
+        ),
+
+        ExecuteMsg::RevealBid { auction_id, amount, salt } => try_reveal_bid(
+            deps,
+            env,
+            &info.sender,
+            auction_id,
+            amount,
+            salt
+        ),
+
+        ExecuteMsg::Settle { auction_id } => try_settle(
+            deps,
+            env,
+            auction_id
+        ),
+
+        ExecuteMsg::CreateRfq { want, pay_asset, expires } => try_create_rfq(
+            deps,
+            env,
+            &info.sender,
+            want,
+            pay_asset,
+            expires
+        ),
+
+        ExecuteMsg::CancelRfq { rfq_id } => try_cancel_rfq(
+            deps,
+            &info.sender,
+            rfq_id
+        ),
+
+        ExecuteMsg::Quote { rfq_id, pay_amount } => try_quote(
+            deps,
+            env,
+            &info.sender,
+            rfq_id,
+            pay_amount,
+            Balance::from(info.funds)
+        ),
+
+        ExecuteMsg::WithdrawQuote { rfq_id } => try_withdraw_quote(
+            deps,
+            &info.sender,
+            rfq_id
+        ),
+
+        ExecuteMsg::AcceptQuote { rfq_id, maker } => try_accept_quote(
+            deps,
+            env,
+            &info.sender,
+            rfq_id,
+            maker,
+            Balance::from(info.funds)
+        ),
+        
+        ExecuteMsg::ConfirmDeal { deal_id } => try_confirm_deal(
+            deps,
+            &info.sender,
+            deal_id
+        ),
+
+        ExecuteMsg::ReleaseDeal { deal_id } => try_release_deal(
+            deps,
+            env,
+            deal_id
+        ),
+
+        ExecuteMsg::DisputeDeal { deal_id } => try_dispute_deal(
+            deps,
+            env,
+            &info.sender,
+            deal_id
+        ),
+
+        ExecuteMsg::ResolveDeal { deal_id, seller_bps } => try_resolve_deal(
+            deps,
+            &info.sender,
+            deal_id,
+            seller_bps
+        ),
+
+        ExecuteMsg::ClaimVested { position_id } => try_claim_vested(
+            deps,
+            env,
+            &info.sender,
+            position_id
+        ),
+
+        ExecuteMsg::CreateProgram(msg) => try_create_program(
+            deps,
+            env,
+            &info.sender,
+            Balance::from(info.funds),
+            msg
+        ),
+
+        ExecuteMsg::CancelProgram { program_id } => try_cancel_program(
+            deps,
+            &info.sender,
+            program_id
+        ),
+
+        ExecuteMsg::Crank { program_ids } => try_crank(
+            deps,
+            env,
+            &info.sender,
+            program_ids
+        ),
+
+        ExecuteMsg::TransferNft { recipient, token_id } => try_nft(
+            deps,
+            env,
+            info,
+            Cw721Msg::TransferNft { recipient, token_id }
+        ),
+
+        ExecuteMsg::SendNft { contract, token_id, msg } => try_nft(
+            deps,
+            env,
+            info,
+            Cw721Msg::SendNft { contract, token_id, msg }
+        ),
+
+        ExecuteMsg::Approve { spender, token_id, expires } => try_nft(
+            deps,
+            env,
+            info,
+            Cw721Msg::Approve { spender, token_id, expires }
+        ),
+
+        ExecuteMsg::Revoke { spender, token_id } => try_nft(
+            deps,
+            env,
+            info,
+            Cw721Msg::Revoke { spender, token_id }
+        ),
+
+        ExecuteMsg::ApproveAll { operator, expires } => try_nft(
+            deps,
+            env,
+            info,
+            Cw721Msg::ApproveAll { operator, expires }
+        ),
+
+        ExecuteMsg::RevokeAll { operator } => try_nft(
+            deps,
+            env,
+            info,
+            Cw721Msg::RevokeAll { operator }
+        ),
+
+        ExecuteMsg::Match { maker_id, taker_id } => try_match(
+            deps,
+            env,
+            maker_id,
+            taker_id
+        ),
+
+        ExecuteMsg::BatchMatch { matches } => try_batch_match(
+            deps,
+            env,
+            matches
+        ),
+
+        ExecuteMsg::AddHook { addr } => try_add_hook(
+            deps,
+            &info.sender,
+            addr
+        ),
+
+        ExecuteMsg::RemoveHook { addr } => try_remove_hook(
+            deps,
+            &info.sender,
+            addr
+        ),
+
+        ExecuteMsg::SetMemberGroup { group } => try_set_member_group(
+            deps,
+            &info.sender,
+            group
+        ),
+
+        ExecuteMsg::UpdateDenyList { add, remove } => try_update_deny_list(
+            deps,
+            &info.sender,
+            add,
+            remove
+        ),
+
+        ExecuteMsg::UpdateAllowList { add, remove, enforce } => try_update_allow_list(
+            deps,
+            &info.sender,
+            add,
+            remove,
+            enforce
+        ),
+
+        ExecuteMsg::UpdateListingRules { deposit, max_open_otcs, min_notional } => try_update_listing_rules(
+            deps,
+            &info.sender,
+            deposit,
+            max_open_otcs,
+            min_notional
+        ),
+
+        ExecuteMsg::TopUpCredit {} => try_top_up_credit(
+            deps,
+            &info.sender,
+            info.funds
+        ),
+
+        ExecuteMsg::WithdrawCredit {} => try_withdraw_credit(
+            deps,
+            &info.sender
+        ),
+
+        ExecuteMsg::CleanupExpired { limit } => try_cleanup_expired(
+            deps,
+            env,
+            &info.sender,
+            limit
+        ),
+
+        ExecuteMsg::UpdateConfig { active, price_improvement, referral_fee } => try_update_config(
+            deps,
+            &info.sender,
+            active,
+            price_improvement,
+            referral_fee
         ),
         
         ExecuteMsg::Receive(msg) => {
@@ -96,6 +401,52 @@ pub fn execute(
 }
 
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        HOOK_REPLY_ID => hook_reply(msg),
+        id => Err(ContractError::Std(StdError::generic_err(format!("Unknown reply id: {}", id))))
+    }
+}
+
+
+pub fn try_update_config(
+    deps: DepsMut,
+    sender: &Addr,
+    active: Option<bool>,
+    price_improvement: Option<PriceImprovement>,
+    referral_fee: Option<Decimal>,
+) -> Result<Response, ContractError> {
+
+    let mut config = STATE.load(deps.storage)?;
+
+    if deps.api.addr_canonicalize(sender.as_str())? != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if let Some(active) = active {
+        config.active = active;
+    }
+
+    if let Some(price_improvement) = price_improvement {
+        config.price_improvement = match price_improvement {
+            PriceImprovement::Collector(collector) => {
+                PriceImprovement::Collector(deps.api.addr_validate(collector.as_str())?)
+            },
+            PriceImprovement::Taker => PriceImprovement::Taker
+        };
+    }
+
+    if let Some(referral_fee) = referral_fee {
+        config.referral_fee = validate_referral_fee(referral_fee)?;
+    }
+
+    STATE.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attribute("method", "update_config"))
+}
+
+
 pub fn execute_receive(
     deps: DepsMut,
     env: Env,
@@ -104,6 +455,9 @@ pub fn execute_receive(
 ) -> Result<Response, ContractError> {
     let msg : ReceiveMsg = from_binary(&wrapper.msg)?;
 
+    check_allowed_addresses(deps.storage, [&deps.api.addr_validate(&wrapper.sender)?])?;
+    check_allowed_assets(deps.storage, [&Denom::Cw20(info.sender.clone())])?;
+
     let balance = Balance::Cw20(Cw20CoinVerified {
         address: info.sender,
         amount: wrapper.amount,
@@ -113,24 +467,82 @@ pub fn execute_receive(
 
     match msg {
         ReceiveMsg::Create(msg) => { 
+            let seller = api.addr_validate(&wrapper.sender)?;
+            let deposit = take_credit(deps.storage, &seller)?;
             try_create_otc(
                 deps, 
                 env,
-                &api.addr_validate(&wrapper.sender)?,
+                &seller,
                 balance,
-                msg.ask_balance, 
-                msg.expires,
-                msg.user_info,
-                msg.description
+                deposit,
+                msg
             )
         }
-        ReceiveMsg::Swap { otc_id } => {
+        ReceiveMsg::Swap { otc_id, recipient, payout_msg, referrer } => {
             try_swap(
                 deps, 
+                env,
                 &api.addr_validate(&wrapper.sender)?, 
                 otc_id,
                 balance,
-                false
+                SwapOptions { recipient, payout_msg, referrer }
+            )
+        }
+        ReceiveMsg::CreateAuction(msg) => {
+            try_create_auction(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                balance,
+                msg
+            )
+        }
+        ReceiveMsg::CreateProgram(msg) => {
+            try_create_program(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                balance,
+                msg
+            )
+        }
+        ReceiveMsg::Bid { auction_id } => {
+            try_bid(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                auction_id,
+                balance
+            )
+        }
+        ReceiveMsg::CommitBid { auction_id, commitment } => {
+            try_commit_bid(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                auction_id,
+                commitment,
+                balance
+            )
+        }
+        ReceiveMsg::Quote { rfq_id, pay_amount } => {
+            try_quote(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                rfq_id,
+                pay_amount,
+                balance
+            )
+        }
+        ReceiveMsg::AcceptQuote { rfq_id, maker } => {
+            try_accept_quote(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                rfq_id,
+                maker,
+                balance
             )
         }
     }
@@ -145,10 +557,8 @@ pub fn try_create_otc(
     env: Env,
     seller: &Addr,
     sell_balance: Balance,
-    ask_balance: Balance,
-    expires: Option<Expiration>,
-    user_info: Option<UserInfo>,
-    description: Option<String>,
+    deposit: Option<Coin>,
+    msg: NewOTC,
     ) -> Result<Response, ContractError> {
     
 
@@ -163,7 +573,7 @@ pub fn try_create_otc(
         ));
     }
 
-    let expires = expires.unwrap_or_default();
+    let expires = msg.expires.unwrap_or_default();
     if expires.is_expired(&env.block) {
         return Err(ContractError::Expired {});
     }
@@ -171,78 +581,138 @@ pub fn try_create_otc(
     let mut new_otc = OTCInfo {
         seller: deps.api.addr_canonicalize(seller.as_str())?,
         sell_native: false,
-        sell_amount: Uint128::from(0 as u8),
+        sell_amount: Uint128::zero(),
         sell_denom: None,
         sell_address: None,
         ask_native: false,
-        ask_amount: Uint128::from(0 as u8),
+        ask_amount: Uint128::zero(),
         ask_denom: None,
         ask_address: None,
         expires,
-        user_info,
-        description
+        user_info: msg.user_info,
+        description: msg.description,
+        dutch_auction: None,
+        recipient: msg.recipient.map(|r| deps.api.addr_validate(&r)).transpose()?,
+        payout_msg: msg.payout_msg,
+        referrer: msg.referrer.map(|r| deps.api.addr_validate(&r)).transpose()?,
+        payout_split: None,
+        pegged_price: None,
+        condition: msg.condition.map(|c| validate_condition(deps.api, c)).transpose()?,
+        escrow: msg.escrow.map(|escrow| -> StdResult<_> {
+            Ok(Escrow {
+                arbiter: escrow.arbiter.map(|a| deps.api.addr_validate(a.as_str())).transpose()?,
+                confirm_period: escrow.confirm_period
+            })
+        }).transpose()?,
+        vesting: None,
+        fills_left: None,
+        min_member_weight: msg.min_member_weight,
+        deposit,
     };
 
+    if let Some(vesting) = msg.vesting {
+        if new_otc.escrow.is_some() {
+            return Err(ContractError::InvalidVesting { 
+                reason: "can't be combined with an escrow".to_string() 
+            });
+        }
+        validate_vesting(&vesting)?;
+        new_otc.vesting = Some(vesting);
+    }
 
-    match sell_balance {
-        Balance::Native(mut balance) => {
-            let coin = balance.0.pop().unwrap();
+    if let Some(split) = msg.payout_split {
+        if new_otc.recipient.is_some() || new_otc.payout_msg.is_some() {
+            return Err(ContractError::InvalidPayoutSplit { 
+                reason: "can't be combined with a recipient or payout message".to_string() 
+            });
+        }
+        new_otc.payout_split = Some(validate_payout_split(deps.api, split)?);
+    }
 
 
-            if balance.0.len() != 0 {
-                return Err(ContractError::Std(
-                    StdError::GenericErr { 
-                        msg: "Cannot create an otc with mupltiple denoms".to_string() 
-                    }
-                ));
-            }
+    let (asset, amount) = single_asset(sell_balance)?;
+    new_otc.sell_amount = amount;
+    match asset {
+        Denom::Native(denom) => {
             new_otc.sell_native = true;
-            new_otc.sell_amount = coin.amount;
-            new_otc.sell_denom = Some(coin.denom);
+            new_otc.sell_denom = Some(denom);
         },
-        Balance::Cw20(token) => {
+        Denom::Cw20(address) => {
             new_otc.sell_native = false;
-            new_otc.sell_amount = token.amount;
-            new_otc.sell_address = Some(token.address);
+            new_otc.sell_address = Some(address);
         }
     };
 
 
-    match ask_balance {
-        Balance::Native(mut balance) => {
+    if let Some(repeat) = msg.repeat {
+        let invalid = |reason: &str| Err(ContractError::InvalidRepeat { reason: reason.to_string() });
 
-            let coin = balance.0.pop().unwrap();
+        if repeat == 0 { return invalid("must be at least one fill"); }
+        if !(new_otc.sell_amount % Uint128::from(repeat)).is_zero() {
+            return invalid("sent amount must split evenly between the fills");
+        }
+        new_otc.sell_amount /= Uint128::from(repeat);
+        new_otc.fills_left = Some(repeat);
+    }
 
-            if balance.0.len() != 0 { return Err(ContractError::TooManyDenoms{}); }
 
+    let (asset, amount) = single_asset(msg.ask_balance)?;
+    new_otc.ask_amount = amount;
+    match asset {
+        Denom::Native(denom) => {
             new_otc.ask_native = true;
-            new_otc.ask_amount = coin.amount;
-            new_otc.ask_denom = Some(coin.denom);
+            new_otc.ask_denom = Some(denom);
         },
-        Balance::Cw20(token) => {
+        Denom::Cw20(address) => {
             new_otc.ask_native = false;
-            new_otc.ask_amount = token.amount;
-            new_otc.ask_address = Some(token.address);
+            new_otc.ask_address = Some(address);
         }
     };
 
 
-    while OTCS.has(deps.storage, config.index) {
-        // okay for ~4 billion
-        config.index += 1;    
+    check_allowed_addresses(deps.storage, [seller].into_iter().chain(new_otc.payees()))?;
+    check_allowed_assets(deps.storage, [&new_otc.sell_asset(), &new_otc.ask_asset()])?;
+
+    if let Some(auction) = msg.dutch_auction {
+        validate_dutch_auction(&auction, new_otc.ask_amount)?;
+        new_otc.dutch_auction = Some(auction);
+    }
+
+    if let Some(peg) = msg.pegged_price {
+        if new_otc.dutch_auction.is_some() {
+            return Err(ContractError::InvalidPeggedPrice { 
+                reason: "can't be combined with a dutch auction".to_string() 
+            });
+        }
+        new_otc.pegged_price = Some(new_pegged_price(deps.api, &deps.querier, &env, &new_otc, peg)?);
+        new_otc.ask_amount = ask_amount(&deps.querier, &env, &new_otc)?;
     }
 
-    OTCS.save(deps.storage, config.index, &new_otc)?;
+
+    check_listing(deps.storage, &new_otc.seller, &new_otc)?;
+
+    let otc_id = config.index;
+
+    save_otc(deps.storage, otc_id, &new_otc)?;
+    // ids are never reused, okay for ~4 billion
+    config.index += 1;
     STATE.save(deps.storage, &config)?; 
+
+    let hooks = hook_msgs(deps.storage, OtcHookMsg::Created { 
+        otc_id, 
+        otc: new_otc.clone() 
+    })?;
    
 
     let data = NewOTCResponse {
-        id: config.index,
+        id: otc_id,
         otc: new_otc
     };
 
     Ok(Response::new()
         .set_data(to_binary(&data).unwrap())
+        .add_submessages(hooks)
+        .add_event(otc_created(data.id, seller, &data.otc))
         .add_attribute("method", "create_new_otc")
     )
 
@@ -250,21 +720,130 @@ pub fn try_create_otc(
 
 
 
-pub fn try_swap(
-    deps: DepsMut,
+fn validate_dutch_auction(
+    auction: &DutchAuction,
+    start_ask_amount: Uint128
+) -> Result<(), ContractError> {
+
+    let invalid = |reason: &str| Err(ContractError::InvalidDutchAuction { reason: reason.to_string() });
+
+    let ordered = match (auction.start, auction.end) {
+        (Scheduled::AtHeight(start), Scheduled::AtHeight(end)) => start < end,
+        (Scheduled::AtTime(start), Scheduled::AtTime(end)) => start < end,
+        _ => return invalid("start and end must both be heights or both be times")
+    };
+
+    if !ordered { return invalid("end must be after start"); }
+
+    if auction.end_ask_amount >= start_ask_amount {
+        return invalid("end ask amount must be smaller than the starting ask amount");
+    }
+
+    if auction.steps == Some(0) { return invalid("steps must be positive"); }
+
+    Ok(())
+}
+
+
+
+// upper bound for the sends of a single payout
+const MAX_PAYOUT_SHARES: usize = 10;
+
+
+fn validate_payout_split(
+    api: &dyn Api,
+    split: Vec<PayoutShare>
+) -> Result<Vec<PayoutShare>, ContractError> {
+
+    let invalid = |reason: &str| Err(ContractError::InvalidPayoutSplit { reason: reason.to_string() });
+
+    if split.is_empty() { return invalid("must have at least one share"); }
+    if split.len() > MAX_PAYOUT_SHARES { return invalid("too many shares"); }
+    if split.iter().any(|share| share.bps == 0) { return invalid("shares must be positive"); }
+
+    let total: u32 = split.iter().map(|share| share.bps as u32).sum();
+    if total != TOTAL_BPS as u32 { return invalid("shares must add up to 10000 bps"); }
+
+    split
+        .into_iter()
+        .map(|share| Ok(PayoutShare {
+            address: api.addr_validate(share.address.as_str())?,
+            bps: share.bps
+        }))
+        .collect()
+}
+
+
+
+/// Optional parameters of a swap given by the buyer
+#[derive(Default)]
+pub struct SwapOptions {
+    pub recipient: Option<String>,
+    pub payout_msg: Option<Binary>,
+    pub referrer: Option<String>,
+}
+
+
+/// Settlement of a swap before anything is written
+pub(crate) struct SwapOutcome {
+    ask_amount: Uint128,
+    refund_amount: Uint128,
+    sell_amount: Uint128,
+    seller_fee: Uint128,
+    buyer_fee: Uint128,
+    // referral fees to record as earned
+    referrals: Vec<(Addr, Denom, Uint128)>,
+    // payouts to the seller, the refund is sent in between
+    seller_payouts: Vec<CosmosMsg>,
+    refund: Option<CosmosMsg>,
+    // payouts to the buyer and the referrers
+    buyer_payouts: Vec<CosmosMsg>,
+    // owner and amount of the sold asset held as a position or deal instead of paid out to the buyer
+    held: Option<(Addr, Uint128)>,
+    // payouts held in a deal until the off-chain leg is confirmed
+    escrowed: bool,
+    // listing deposit returned on the last fill
+    deposit: Option<CosmosMsg>,
+}
+
+
+impl SwapOutcome {
+    /// Settlement without the refund
+    fn payouts(&self) -> Vec<CosmosMsg> {
+        self.seller_payouts.iter().chain(&self.buyer_payouts).cloned().collect()
+    }
+
+    /// Sent by the swap itself, escrow otcs only send back the refund and the deposit
+    fn messages(&self) -> Vec<CosmosMsg> {
+        let payouts = if self.escrowed { vec!() } else { self.seller_payouts.clone() };
+        let buyer_payouts = if self.escrowed { vec!() } else { self.buyer_payouts.clone() };
+
+        payouts.into_iter()
+            .chain(self.refund.clone())
+            .chain(buyer_payouts)
+            .chain(self.deposit.clone())
+            .collect()
+    }
+}
+
+
+/// Validation and settlement math of a swap shared by execution and simulation
+pub(crate) fn compute_swap(
+    deps: Deps,
+    env: &Env,
     payer: &Addr,
     otc_id: u32,
     balance: Balance,
-    native: bool,
-    ) -> Result<Response, ContractError> {
-    
+    options: SwapOptions,
+    ) -> Result<SwapOutcome, ContractError> {
+
     let otc_info = OTCS.load(deps.storage, otc_id)?;
 
-    if otc_info.ask_native ^ native { return Err(ContractError::WrongDenom {}); }
+    if otc_info.expires.is_expired(&env.block) { return Err(ContractError::OtcExpired {}); }
 
     let seller = deps.api.addr_humanize(&otc_info.seller)?;
 
-    if &seller == payer {
+    if seller == *payer {
         return Err(ContractError::Std(
             StdError::GenericErr { 
                 msg: "Can't swap with yourself".to_string() 
@@ -272,81 +851,234 @@ pub fn try_swap(
         ));
     }
 
+    let (asset, amount) = single_asset(balance)?;
 
-    let payment_1 : CosmosMsg = if native {
-        let mut casted =  cast!(balance, Balance::Native);
-        let coin = casted.0.pop().unwrap();
+    if asset != otc_info.ask_asset() { return Err(ContractError::WrongDenom {}); }
 
-        if casted.0.len() != 0 { return Err(ContractError::TooManyDenoms{}); }
-        if coin.denom != otc_info.ask_denom.unwrap() { return Err(ContractError::WrongDenom {}); }
+    check_counterparty(deps, &otc_info, payer)?;
 
-        if coin.amount < otc_info.ask_amount {
-            return Err(ContractError::Std(
-                StdError::GenericErr { 
-                    msg: "Sent amount is smaller than what being asked".to_string() 
-                }
-            ));
-        }
+    check_condition(&deps.querier, env, &otc_info)?;
 
-        CosmosMsg::Bank(BankMsg::Send { to_address: seller.into_string(), amount: vec!(coin) })
-        
+    let ask_amount = ask_amount(&deps.querier, env, &otc_info)?;
 
+    if amount < ask_amount {
+        return Err(ContractError::Std(
+            StdError::GenericErr { 
+                msg: "Sent amount is smaller than what being asked".to_string() 
+            }
+        ));
+    }
+
+    let recipient = match options.recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => payer.clone()
+    };
+
+    if recipient != *payer {
+        check_counterparty(deps, &otc_info, &recipient)?;
+    }
+
+    let buyer_referrer = options.referrer.map(|r| deps.api.addr_validate(&r)).transpose()?;
+
+    check_allowed_addresses(
+        deps.storage, 
+        [payer, &recipient, &seller].into_iter().chain(otc_info.payees()).chain(buyer_referrer.as_ref())
+    )?;
+    check_allowed_assets(deps.storage, [&otc_info.sell_asset(), &otc_info.ask_asset()])?;
+
+    let config = STATE.load(deps.storage)?;
+    let seller_fee = referral_fee(config.referral_fee, otc_info.referrer.as_ref(), ask_amount);
+    let buyer_fee = referral_fee(config.referral_fee, buyer_referrer.as_ref(), otc_info.sell_amount);
+
+    // refund whatever was sent above the current ask
+    let refund_amount = amount - ask_amount;
+    let refund = if refund_amount.is_zero() {
+        None
     } else {
-        let casted = cast!(balance, Balance::Cw20);
+        Some(send_asset_msg(&asset, refund_amount, payer.as_str())?)
+    };
 
-        if casted.address != otc_info.ask_address.unwrap() { return Err(ContractError::WrongDenom {}); }
+    let seller_payouts = otc_info.payout_msgs(deps.api, &asset, ask_amount - seller_fee)?;
 
-        if casted.amount < otc_info.ask_amount {
-            return Err(ContractError::Std(
-                StdError::GenericErr { 
-                    msg: "Send amount is smaller than what being asked".to_string() 
-                }
-            ));
-        }
+    let mut messages = vec!();
 
-        CosmosMsg::Wasm(WasmMsg::Execute { 
-            contract_addr: casted.address.to_string(), 
-            msg: to_binary(&Cw20ExecuteMsg::Transfer { recipient: seller.to_string(), amount: casted.amount })?, 
-            funds: vec!()
-        })
-        
+    // vesting and escrow otcs keep the sold asset for the owner of the claim token
+    let held = if otc_info.vesting.is_some() || otc_info.escrow.is_some() {
+        if options.payout_msg.is_some() { return Err(ContractError::HeldPayoutMsg {}); }
+        Some((recipient, otc_info.sell_amount - buyer_fee))
+    } else {
+        messages.push(payout_msg(&otc_info.sell_asset(), otc_info.sell_amount - buyer_fee, recipient.as_str(), options.payout_msg)?);
+        None
     };
 
+    let mut referrals = vec!();
+    for (referrer, denom, fee) in [
+        (otc_info.referrer.clone(), asset, seller_fee),
+        (buyer_referrer, otc_info.sell_asset(), buyer_fee)
+    ] {
+        if let Some(referrer) = referrer.filter(|_| !fee.is_zero()) {
+            messages.push(send_asset_msg(&denom, fee, referrer.as_str())?);
+            referrals.push((referrer, denom, fee));
+        }
+    }
 
-    let payment_2 : CosmosMsg = if otc_info.sell_native {
-        CosmosMsg::Bank(BankMsg::Send { 
-            to_address: payer.clone().into_string(), 
-            amount: vec!(Coin { denom: otc_info.sell_denom.unwrap(), amount: otc_info.sell_amount }) 
-        })
+    // recurring otcs keep their deposit until the last fill
+    let deposit = if matches!(otc_info.fills_left, Some(fills_left) if fills_left > 1) {
+        None
     } else {
-        CosmosMsg::Wasm(WasmMsg::Execute { 
-            contract_addr: otc_info.sell_address.unwrap().to_string(), 
-            msg: to_binary(&Cw20ExecuteMsg::Transfer { 
-                recipient: payer.to_string(), 
-                amount: otc_info.sell_amount 
-            })?, 
-            funds: vec!()
-        })
+        deposit_msg(&otc_info, seller.as_str())
     };
 
+    Ok(SwapOutcome { 
+        ask_amount, 
+        refund_amount, 
+        sell_amount: otc_info.sell_amount, 
+        seller_fee,
+        buyer_fee,
+        referrals,
+        seller_payouts,
+        refund,
+        buyer_payouts: messages,
+        held,
+        escrowed: otc_info.escrow.is_some(),
+        deposit,
+    })
+}
+
+
 
-    OTCS.remove(deps.storage, otc_id);
+pub fn try_swap(
+    deps: DepsMut,
+    env: Env,
+    payer: &Addr,
+    otc_id: u32,
+    balance: Balance,
+    options: SwapOptions,
+    ) -> Result<Response, ContractError> {
     
+    let outcome = compute_swap(deps.as_ref(), &env, payer, otc_id, balance, options)?;
 
-    Ok(Response::new()
-        .add_messages(vec!(
-            payment_1,
-            payment_2
-        ))
+    let otc_info = OTCS.load(deps.storage, otc_id)?;
+    let seller = deps.api.addr_humanize(&otc_info.seller)?;
+
+    // recurring otcs keep their id until the last fill
+    match otc_info.fills_left {
+        Some(fills_left) if fills_left > 1 => {
+            save_otc(deps.storage, otc_id, &OTCInfo { fills_left: Some(fills_left - 1), ..otc_info.clone() })?;
+        },
+        _ => remove_otc(deps.storage, otc_id)?
+    };
+
+    let hooks = hook_msgs(deps.storage, OtcHookMsg::Filled { 
+        otc_id, 
+        buyer: payer.to_string(), 
+        sell_amount: outcome.sell_amount, 
+        ask_amount: outcome.ask_amount 
+    })?;
+
+    let filled = otc_filled(otc_id, &otc_info, &seller, payer, Fill {
+        sell_amount: outcome.sell_amount,
+        ask_amount: outcome.ask_amount,
+        fee: outcome.seller_fee,
+        buyer_fee: outcome.buyer_fee,
+    });
+
+    let referrals = outcome.referrals.clone();
+
+    // escrow otcs hold the payouts until the off-chain leg is confirmed
+    let res = match &otc_info.escrow {
+        Some(escrow) => {
+            let (buyer, delivery) = outcome.held.clone().unwrap_or((payer.clone(), Uint128::zero()));
+            let deal = Deal {
+                otc_id,
+                sell_asset: Asset { denom: otc_info.sell_asset(), amount: outcome.sell_amount },
+                payment: Asset { denom: otc_info.ask_asset(), amount: outcome.ask_amount },
+                seller,
+                buyer,
+                delivery,
+                arbiter: escrow.arbiter.clone(),
+                deadline: escrow.confirm_period.after(&env.block),
+                disputed: false,
+                referrals,
+                release: outcome.payouts(),
+                min_member_weight: otc_info.min_member_weight,
+            };
+
+            Response::new()
+                .add_messages(outcome.messages())
+                .add_event(filled)
+                .add_event(open_deal(deps.storage, &deal)?)
+        },
+        None => {
+            for (referrer, denom, fee) in &referrals {
+                record_referral(deps.storage, referrer, denom, *fee)?;
+            }
+
+            let mut res = Response::new().add_event(filled);
+
+            if let (Some((owner, amount)), Some(schedule)) = (outcome.held.clone(), otc_info.vesting.clone()) {
+                let position = Position {
+                    otc_id,
+                    owner,
+                    asset: Asset { denom: otc_info.sell_asset(), amount },
+                    start: vesting_start(&schedule, &env.block),
+                    schedule,
+                    claimed: Uint128::zero(),
+                    min_member_weight: otc_info.min_member_weight,
+                };
+                res = res.add_event(open_position(deps.storage, &position)?);
+            }
+
+            res.add_messages(outcome.messages())
+        }
+    };
+
+    Ok(res
+        .add_submessages(hooks)
         .add_attribute("method", "swap")
     )
 }
 
 
 
+pub fn try_cancel(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    otc_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let otc_info = OTCS.load(deps.storage, otc_id)?;
+
+    let seller = deps.api.addr_humanize(&otc_info.seller)?;
+    let expired = otc_info.expires.is_expired(&env.block);
+
+    // expired otcs can be reclaimed by anyone, who keeps the listing deposit
+    let mut closed = otc_closed(otc_id, &otc_info, &seller, expired);
+    if *sender != seller {
+        if !expired { return Err(ContractError::Unauthorized {}); }
+        check_allowed_addresses(deps.storage, [sender])?;
+        closed = closed.add_attribute("reclaimed_by", sender);
+    }
+
+    remove_otc(deps.storage, otc_id)?;
+
+    Ok(Response::new()
+        .add_message(send_asset_msg(&otc_info.sell_asset(), otc_info.escrowed(), seller.as_str())?)
+        .add_messages(deposit_msg(&otc_info, sender.as_str()))
+        .add_submessages(hook_msgs(deps.storage, OtcHookMsg::Cancelled { otc_id })?)
+        .add_event(closed)
+        .add_attribute("method", "cancel")
+    )
+}
+
+
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
+        QueryMsg::GetConfig {} => to_binary(&STATE.load(deps.storage)?),
+
         QueryMsg::GetOtcs {
             include_expired, 
             start_after, 
@@ -357,7 +1089,232 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             include_expired.unwrap_or_default(),
             start_after,
             limit
-        )?)
+        )?),
+
+        QueryMsg::Fillable { otc_id } => to_binary(&query_fillable(
+            deps,
+            env,
+            otc_id
+        )?),
+
+        QueryMsg::SimulateSwap { 
+            otc_id, 
+            sender, 
+            offer_asset,
+            recipient,
+            payout_msg,
+            referrer
+        } => to_binary(&query_simulate_swap(
+            deps, 
+            env, 
+            otc_id, 
+            sender, 
+            offer_asset,
+            SwapOptions { recipient, payout_msg, referrer }
+        )?),
+
+        QueryMsg::CurrentPrice { otc_id } => to_binary(&query_current_price(
+            deps, 
+            env, 
+            otc_id
+        )?),
+
+        QueryMsg::GetAuction { auction_id } => to_binary(&AUCTIONS.load(deps.storage, auction_id)?),
+
+        QueryMsg::GetAuctions {
+            include_ended,
+            start_after,
+            limit
+        } => to_binary(&query_auctions(
+            deps,
+            env,
+            include_ended.unwrap_or_default(),
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::GetSealedBids {
+            auction_id,
+            start_after,
+            limit
+        } => to_binary(&query_sealed_bids(
+            deps,
+            auction_id,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::GetDeal { deal_id } => to_binary(&DEALS.load(deps.storage, deal_id)?),
+
+        QueryMsg::GetDeals {
+            start_after,
+            limit
+        } => to_binary(&query_deals(
+            deps,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::GetPosition { position_id } => to_binary(&query_position(
+            deps,
+            env,
+            position_id
+        )?),
+
+        QueryMsg::GetPositions {
+            owner,
+            start_after,
+            limit
+        } => to_binary(&query_positions(
+            deps,
+            env,
+            owner,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::GetProgram { program_id } => to_binary(&PROGRAMS.load(deps.storage, program_id)?),
+
+        QueryMsg::GetPrograms {
+            owner,
+            due_only,
+            start_after,
+            limit
+        } => to_binary(&query_programs(
+            deps,
+            env,
+            owner,
+            due_only,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::PairBook {
+            sell_asset,
+            ask_asset,
+            start_after,
+            limit
+        } => to_binary(&query_pair_book(
+            deps,
+            env,
+            sell_asset,
+            ask_asset,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::Pairs { start_after, limit } => to_binary(&query_pairs(deps, start_after, limit)?),
+
+        QueryMsg::BestOffer {
+            sell_asset,
+            ask_asset
+        } => to_binary(&query_best_offer(
+            deps,
+            env,
+            sell_asset,
+            ask_asset
+        )?),
+
+        QueryMsg::OffersByPrice {
+            sell_asset,
+            ask_asset,
+            min_price,
+            max_price,
+            limit
+        } => to_binary(&query_offers_by_price(
+            deps,
+            env,
+            sell_asset,
+            ask_asset,
+            min_price,
+            max_price,
+            limit
+        )?),
+
+        QueryMsg::GetRfqs {
+            include_expired,
+            start_after,
+            limit
+        } => to_binary(&query_rfqs(
+            deps,
+            env,
+            include_expired.unwrap_or_default(),
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::GetQuotes {
+            rfq_id,
+            start_after,
+            limit
+        } => to_binary(&query_quotes(
+            deps,
+            rfq_id,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::ReferralEarnings { address } => to_binary(&query_referral_earnings(deps, address)?),
+
+        QueryMsg::Hooks {} => to_binary(&HOOKS.query_hooks(deps)?),
+
+        QueryMsg::ListingRules {} => to_binary(&query_listing_rules(deps)?),
+
+        QueryMsg::ListingCredit { address } => to_binary(&query_listing_credit(deps, address)?),
+
+        QueryMsg::ExpiredCount {} => to_binary(&query_expired_count(deps, env)?),
+
+        QueryMsg::DenyList { start_after, limit } => to_binary(&query_deny_list(deps, start_after, limit)?),
+
+        QueryMsg::AllowList { start_after, limit } => to_binary(&query_allow_list(deps, start_after, limit)?),
+
+        QueryMsg::OwnerOf { token_id, include_expired } => query_nft(deps, env, Cw721QueryMsg::OwnerOf { 
+            token_id, 
+            include_expired 
+        }),
+
+        QueryMsg::Approval { token_id, spender, include_expired } => query_nft(deps, env, Cw721QueryMsg::Approval { 
+            token_id, 
+            spender, 
+            include_expired 
+        }),
+
+        QueryMsg::Approvals { token_id, include_expired } => query_nft(deps, env, Cw721QueryMsg::Approvals { 
+            token_id, 
+            include_expired 
+        }),
+
+        QueryMsg::Operator { owner, operator, include_expired } => query_nft(deps, env, Cw721QueryMsg::Operator { 
+            owner, 
+            operator, 
+            include_expired 
+        }),
+
+        QueryMsg::AllOperators { owner, include_expired, start_after, limit } => query_nft(deps, env, Cw721QueryMsg::AllOperators { 
+            owner, 
+            include_expired, 
+            start_after, 
+            limit 
+        }),
+
+        QueryMsg::NumTokens {} => query_nft(deps, env, Cw721QueryMsg::NumTokens {}),
+
+        QueryMsg::ContractInfo {} => query_nft(deps, env, Cw721QueryMsg::ContractInfo {}),
+
+        QueryMsg::NftInfo { token_id } => query_nft(deps, env, Cw721QueryMsg::NftInfo { token_id }),
+
+        QueryMsg::AllNftInfo { token_id, include_expired } => query_nft(deps, env, Cw721QueryMsg::AllNftInfo { 
+            token_id, 
+            include_expired 
+        }),
+
+        QueryMsg::Tokens { owner, start_after, limit } => query_nft(deps, env, Cw721QueryMsg::Tokens { 
+            owner, 
+            start_after, 
+            limit 
+        }),
+
+        QueryMsg::AllTokens { start_after, limit } => query_nft(deps, env, Cw721QueryMsg::AllTokens { start_after, limit }),
     }
 }
 
@@ -373,10 +1330,7 @@ fn query_otcs(
 
     let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
     
-    let start = match start_after {
-        Some(start_after) => Some(Bound::exclusive(start_after)),
-        None => None
-    };
+    let start = start_after.map(Bound::exclusive);
 
     let result : StdResult<Vec<_>> = OTCS
     .range(
@@ -400,4 +1354,101 @@ fn query_otcs(
 
 
 
+fn query_current_price(
+    deps: Deps, 
+    env: Env, 
+    otc_id: u32,
+) -> StdResult<CurrentPriceResponse> {
+
+    let otc = OTCS.load(deps.storage, otc_id)?;
+
+    let ask_amount = ask_amount(&deps.querier, &env, &otc)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+
+    Ok(CurrentPriceResponse { 
+        sell_amount: otc.sell_amount, 
+        ask_amount
+    })
+}
+
 
+
+fn query_fillable(
+    deps: Deps, 
+    env: Env, 
+    otc_id: u32,
+) -> StdResult<FillableResponse> {
+
+    let otc = OTCS.load(deps.storage, otc_id)?;
+
+    let fillable = if otc.expires.is_expired(&env.block) {
+        Err(ContractError::OtcExpired {})
+    } else {
+        check_condition(&deps.querier, &env, &otc).and_then(|_| ask_amount(&deps.querier, &env, &otc))
+    };
+
+    Ok(FillableResponse { 
+        fillable: fillable.is_ok(), 
+        reason: fillable.err().map(|err| err.to_string()) 
+    })
+}
+
+
+
+fn query_simulate_swap(
+    deps: Deps, 
+    env: Env, 
+    otc_id: u32,
+    sender: String,
+    offer_asset: Asset,
+    options: SwapOptions,
+) -> StdResult<SimulateSwapResponse> {
+
+    let sender = deps.api.addr_validate(&sender)?;
+
+    let balance = match offer_asset.denom {
+        Denom::Native(denom) => Balance::Native(NativeBalance(vec!(
+            Coin { denom, amount: offer_asset.amount }
+        ))),
+        Denom::Cw20(address) => Balance::Cw20(Cw20CoinVerified { 
+            address, 
+            amount: offer_asset.amount 
+        })
+    };
+
+    Ok(match compute_swap(deps, &env, &sender, otc_id, balance, options) {
+        Ok(outcome) => {
+            // what the deal sends once released, the sold asset to the buyer last
+            let held_messages = match (&outcome.held, outcome.escrowed) {
+                (Some((buyer, delivery)), true) => {
+                    let sell_asset = OTCS.load(deps.storage, otc_id)?.sell_asset();
+                    let mut release = outcome.payouts();
+                    release.push(send_asset_msg(&sell_asset, *delivery, buyer.as_str())?);
+                    release
+                },
+                _ => vec!()
+            };
+
+            SimulateSwapResponse {
+                error: None,
+                ask_amount: outcome.ask_amount,
+                refund_amount: outcome.refund_amount,
+                sell_amount: outcome.sell_amount,
+                seller_fee: outcome.seller_fee,
+                buyer_fee: outcome.buyer_fee,
+                messages: outcome.messages(),
+                held_messages,
+            }
+        },
+        Err(err) => SimulateSwapResponse {
+            error: Some(err.to_string()),
+            ask_amount: Uint128::zero(),
+            refund_amount: Uint128::zero(),
+            sell_amount: Uint128::zero(),
+            seller_fee: Uint128::zero(),
+            buyer_fee: Uint128::zero(),
+            messages: vec!(),
+            held_messages: vec!(),
+        }
+    })
+}
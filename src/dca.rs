@@ -0,0 +1,257 @@
+use cosmwasm_std::{to_binary, Addr, Deps, DepsMut, Env, Order, Response, StdError, StdResult, Uint128};
+use cw20::Balance;
+use cw_storage_plus::Bound;
+use cw_utils::{Duration, Expiration};
+
+use crate::batch::merge;
+use crate::book::offers_by_price;
+use crate::compliance::{check_allowed_addresses, check_allowed_assets};
+use crate::contract::{compute_swap, try_swap, SwapOptions, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::events::{installment_run, program_created, program_ended};
+use crate::msg::{GetProgramsResponse, NewProgram};
+use crate::state::{Asset, Program, PROGRAMS, STATE};
+use crate::utils::{asset_balance, send_asset_msg, single_asset, validate_denom};
+
+
+
+pub fn try_create_program(
+    deps: DepsMut,
+    env: Env,
+    owner: &Addr,
+    balance: Balance,
+    msg: NewProgram,
+) -> Result<Response, ContractError> {
+
+    let mut config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "The factory has been stopped.  No new program can be created".to_string()
+            }
+        ));
+    }
+
+    let invalid = |reason: &str| Err(ContractError::InvalidProgram { reason: reason.to_string() });
+
+    let (pay_asset, sent) = single_asset(balance)?;
+    let buy_asset = validate_denom(deps.api, msg.buy_asset)?;
+
+    if pay_asset == buy_asset { return invalid("can't buy the asset it pays with"); }
+
+    check_allowed_addresses(deps.storage, [owner])?;
+    check_allowed_assets(deps.storage, [&pay_asset, &buy_asset])?;
+
+    if msg.installments == 0 { return invalid("must have at least one installment"); }
+    if msg.amount.is_zero() { return invalid("installment amount must be positive"); }
+    if msg.max_price.is_zero() { return invalid("max price must be positive"); }
+    if matches!(msg.interval, Duration::Height(0) | Duration::Time(0)) { return invalid("interval must be positive"); }
+
+    let needed = (msg.amount + msg.tip).checked_mul(Uint128::from(msg.installments)).map_err(StdError::from)?;
+    if sent != needed {
+        return invalid(&format!("must escrow exactly {} for the installments and tips", needed));
+    }
+
+    let program = Program {
+        owner: owner.clone(),
+        escrow: Asset { denom: pay_asset, amount: sent },
+        buy_asset,
+        amount: msg.amount,
+        max_price: msg.max_price,
+        next: match msg.interval {
+            Duration::Height(_) => Expiration::AtHeight(env.block.height),
+            Duration::Time(_) => Expiration::AtTime(env.block.time)
+        },
+        interval: msg.interval,
+        installments_left: msg.installments,
+        tip: msg.tip,
+    };
+
+    let program_id = config.program_index;
+
+    PROGRAMS.save(deps.storage, program_id, &program)?;
+    config.program_index += 1;
+    STATE.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .set_data(to_binary(&program_id)?)
+        .add_event(program_created(program_id, &program))
+        .add_attribute("method", "create_program")
+    )
+}
+
+
+
+pub fn try_cancel_program(
+    deps: DepsMut,
+    sender: &Addr,
+    program_id: u32,
+) -> Result<Response, ContractError> {
+
+    let program = PROGRAMS.load(deps.storage, program_id)?;
+
+    if program.owner != *sender { return Err(ContractError::Unauthorized {}); }
+
+    PROGRAMS.remove(deps.storage, program_id);
+
+    Ok(Response::new()
+        .add_message(send_asset_msg(&program.escrow.denom, program.escrow.amount, sender.as_str())?)
+        .add_event(program_ended(program_id, &program))
+        .add_attribute("method", "cancel_program")
+    )
+}
+
+
+
+/// Cheapest fixed price otc the installment can fill on its own and settle at once, skipping
+/// vesting, escrow and conditional otcs and those the swap would reject
+fn best_fit(deps: Deps, env: &Env, program: &Program) -> StdResult<Option<(u32, Uint128)>> {
+
+    let offers = offers_by_price(
+        deps,
+        env,
+        program.buy_asset.clone(),
+        program.escrow.denom.clone(),
+        None,
+        Some(program.max_price),
+        MAX_LIMIT as usize
+    )?;
+
+    Ok(offers
+        .into_iter()
+        .find(|(otc_id, otc)| {
+            otc.ask_amount <= program.amount &&
+            otc.vesting.is_none() &&
+            otc.escrow.is_none() &&
+            otc.condition.is_none() &&
+            compute_swap(
+                deps,
+                env,
+                &program.owner,
+                *otc_id,
+                asset_balance(&program.escrow.denom, otc.ask_amount),
+                SwapOptions::default()
+            ).is_ok()
+        })
+        .map(|(id, otc)| (id, otc.ask_amount)))
+}
+
+
+
+/// Runs a due installment filling the best fitting otc, it stays due while none fits
+fn run_installment(
+    mut deps: DepsMut,
+    env: &Env,
+    cranker: &Addr,
+    program_id: u32,
+    mut program: Program,
+) -> Result<Option<Response>, ContractError> {
+
+    let (otc_id, spent) = match best_fit(deps.as_ref(), env, &program)? {
+        Some(fill) => fill,
+        None => return Ok(None)
+    };
+
+    let swapped = try_swap(
+        deps.branch(),
+        env.clone(),
+        &program.owner,
+        otc_id,
+        asset_balance(&program.escrow.denom, spent),
+        SwapOptions::default()
+    )?;
+    let mut res = merge(Response::new(), swapped);
+
+    if !program.tip.is_zero() {
+        res = res.add_message(send_asset_msg(&program.escrow.denom, program.tip, cranker.as_str())?);
+    }
+
+    program.escrow.amount -= spent + program.tip;
+    program.installments_left -= 1;
+
+    res = res.add_event(installment_run(program_id, &program, otc_id, spent));
+
+    if program.installments_left == 0 {
+        PROGRAMS.remove(deps.storage, program_id);
+        if !program.escrow.amount.is_zero() {
+            res = res.add_message(send_asset_msg(&program.escrow.denom, program.escrow.amount, program.owner.as_str())?);
+        }
+        res = res.add_event(program_ended(program_id, &program));
+    } else {
+        program.next = program.interval.after(&env.block);
+        PROGRAMS.save(deps.storage, program_id, &program)?;
+    }
+
+    Ok(Some(res))
+}
+
+
+
+/// Permissionless, programs without an installment due or an otc to fill it are skipped
+pub fn try_crank(
+    mut deps: DepsMut,
+    env: Env,
+    cranker: &Addr,
+    program_ids: Vec<u32>,
+) -> Result<Response, ContractError> {
+
+    // the cranker is paid the tips
+    check_allowed_addresses(deps.storage, [cranker])?;
+
+    let mut res = Response::new().add_attribute("method", "crank");
+    let mut ran = 0;
+
+    for program_id in program_ids {
+        let program = match PROGRAMS.may_load(deps.storage, program_id)? {
+            Some(program) if program.next.is_expired(&env.block) => program,
+            _ => continue
+        };
+
+        if let Some(installment) = run_installment(deps.branch(), &env, cranker, program_id, program)? {
+            res = merge(res, installment);
+            ran += 1;
+        }
+    }
+
+    if ran == 0 { return Err(ContractError::NothingDue {}); }
+
+    Ok(res.add_attribute("installments", ran.to_string()))
+}
+
+
+
+pub fn query_programs(
+    deps: Deps,
+    env: Env,
+    owner: Option<String>,
+    due_only: Option<bool>,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<GetProgramsResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let due_only = due_only.unwrap_or(false);
+
+    let owner = owner.map(|owner| deps.api.addr_validate(&owner)).transpose()?;
+
+    let programs = PROGRAMS
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending
+        )
+        .filter(|program| match program {
+            Ok((_, program)) => {
+                !matches!(&owner, Some(owner) if program.owner != *owner) &&
+                (!due_only || program.next.is_expired(&env.block))
+            },
+            Err(_) => true
+        })
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetProgramsResponse { programs })
+}
+
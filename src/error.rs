@@ -1,4 +1,6 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Coin, Decimal, StdError, Uint128};
+use cw_controllers::HookError;
+use cw721_base::ContractError as Cw721ContractError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,6 +8,12 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Hook(#[from] HookError),
+
+    #[error("{0}")]
+    Cw721(#[from] Cw721ContractError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -18,6 +26,138 @@ pub enum ContractError {
     #[error("Cannot set approval that is already expired")]
     Expired {},
 
+    #[error("The otc has expired")]
+    OtcExpired {},
+
+    #[error("Invalid dutch auction: {reason}")]
+    InvalidDutchAuction { reason: String },
+
+    #[error("No funds sent")]
+    NoFunds {},
+
+    #[error("Invalid auction: {reason}")]
+    InvalidAuction { reason: String },
+
+    #[error("The auction has ended")]
+    AuctionEnded {},
+
+    #[error("The auction has not ended yet")]
+    AuctionNotEnded {},
+
+    #[error("Bid is too low, the minimum is {min}")]
+    BidTooLow { min: Uint128 },
+
+    #[error("Bids on sealed auctions must be committed and revealed")]
+    SealedAuction {},
+
+    #[error("The auction doesn't take sealed bids")]
+    NotSealedAuction {},
+
+    #[error("Bids can only be revealed after the auction end and before the reveal end")]
+    NotRevealPhase {},
+
+    #[error("Revealed bid doesn't match the commitment")]
+    InvalidCommitment {},
+
+    #[error("Too many bids")]
+    TooManyBids {},
+
+    #[error("The rfq has expired")]
+    RfqExpired {},
+
+    #[error("Too many quotes")]
+    TooManyQuotes {},
+
+    #[error("The otcs don't cross")]
+    NotCrossing {},
+
+    #[error("Only fixed price otcs can be matched")]
+    NotMatchable {},
+
+    #[error("Sent funds don't match the batch amounts")]
+    FundsMismatch {},
+
+    #[error("Invalid payout split: {reason}")]
+    InvalidPayoutSplit { reason: String },
+
+    #[error("Invalid pegged price: {reason}")]
+    InvalidPeggedPrice { reason: String },
+
+    #[error("The oracle price is stale")]
+    StalePrice {},
+
+    #[error("The oracle price moved too far from the price at creation")]
+    PriceDeviation {},
+
+    #[error("Invalid condition: {reason}")]
+    InvalidCondition { reason: String },
+
+    #[error("The otc condition doesn't hold: {reason}")]
+    ConditionNotMet { reason: String },
+
+    #[error("The deal is disputed")]
+    DealDisputed {},
+
+    #[error("The deal is not disputed")]
+    NotDisputed {},
+
+    #[error("The deal has no arbiter")]
+    NoArbiter {},
+
+    #[error("The confirmation deadline has not passed yet")]
+    DeadlineNotPassed {},
+
+    #[error("The confirmation deadline has passed")]
+    DeadlinePassed {},
+
+    #[error("Invalid vesting: {reason}")]
+    InvalidVesting { reason: String },
+
+    #[error("Nothing to claim")]
+    NothingToClaim {},
+
+    #[error("No member group is configured")]
+    NoMemberGroup {},
+
+    #[error("Must be a member of the group with a weight of at least {min_weight}")]
+    NotMember { min_weight: u64 },
+
+    #[error("Address {address} is not allowed to trade")]
+    AddressDenied { address: String },
+
+    #[error("Asset {asset} is not on the allow-list")]
+    AssetNotAllowed { asset: String },
+
+    #[error("Listing requires a deposit of {deposit}")]
+    DepositMissing { deposit: Coin },
+
+    #[error("No listing deposit is configured")]
+    NoDeposit {},
+
+    #[error("Can't have more than {max} open otcs")]
+    TooManyOpenOtcs { max: u32 },
+
+    #[error("Amount of {asset} is below the minimum of {min}")]
+    BelowMinNotional { asset: String, min: Uint128 },
+
+    #[error("Invalid program: {reason}")]
+    InvalidProgram { reason: String },
+
+    #[error("None of the programs has an installment due that an otc can fill")]
+    NothingDue {},
+
+    #[error("No expired otc to clean up")]
+    NothingExpired {},
+
+    #[error("Invalid repeat: {reason}")]
+    InvalidRepeat { reason: String },
+
+    #[error("Payout messages can't be used for assets held as a position or deal")]
+    HeldPayoutMsg {},
+
+    #[error("Referral fee can't be above {max}")]
+    InvalidReferralFee { max: Decimal },
+
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
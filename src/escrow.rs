@@ -0,0 +1,205 @@
+use cosmwasm_std::{Addr, Deps, DepsMut, Env, Event, Order, Response, StdResult, Storage};
+use cw_storage_plus::Bound;
+use cw_utils::Duration;
+
+use crate::contract::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::events::{deal_disputed, deal_opened, deal_refunded, deal_resolved};
+use crate::msg::GetDealsResponse;
+use crate::nft::{burn, mint, Claim};
+use crate::referral::record_referral;
+use crate::state::{Deal, DEALS, STATE, TOTAL_BPS};
+use crate::utils::send_asset_msg;
+
+
+
+/// Time the arbiter has to resolve a dispute before the deal can be refunded
+pub const RESOLVE_PERIOD: Duration = Duration::Time(30 * 24 * 60 * 60);
+
+
+
+/// Stores the deal of a swapped escrow otc and mints its token to the buyer
+pub fn open_deal(storage: &mut dyn Storage, deal: &Deal) -> StdResult<Event> {
+    let mut config = STATE.load(storage)?;
+
+    // token ids are never reused
+    let deal_id = config.deal_index;
+
+    DEALS.save(storage, deal_id, deal)?;
+    config.deal_index += 1;
+    STATE.save(storage, &config)?;
+
+    mint(storage, Claim::Deal(deal_id), &deal.buyer)?;
+
+    Ok(deal_opened(deal_id, deal))
+}
+
+
+
+/// Sends the settlement held by the deal, the sold asset to the current buyer
+fn release(deps: DepsMut, deal_id: u32, deal: Deal, event: &str, method: &str) -> Result<Response, ContractError> {
+    DEALS.remove(deps.storage, deal_id);
+    burn(deps.storage, Claim::Deal(deal_id))?;
+
+    for (referrer, denom, fee) in &deal.referrals {
+        record_referral(deps.storage, referrer, denom, *fee)?;
+    }
+
+    // deals opened before the delivery was held apart have it within the release
+    let delivery = if deal.delivery.is_zero() {
+        None
+    } else {
+        Some(send_asset_msg(&deal.sell_asset.denom, deal.delivery, deal.buyer.as_str())?)
+    };
+
+    Ok(Response::new()
+        .add_messages(deal.release)
+        .add_messages(delivery)
+        .add_event(Event::new(event).add_attribute("deal_id", deal_id.to_string()))
+        .add_attribute("method", method)
+    )
+}
+
+
+
+pub fn try_confirm_deal(
+    deps: DepsMut,
+    sender: &Addr,
+    deal_id: u32,
+) -> Result<Response, ContractError> {
+
+    let deal = DEALS.load(deps.storage, deal_id)?;
+
+    if deal.buyer != *sender { return Err(ContractError::Unauthorized {}); }
+
+    release(deps, deal_id, deal, "deal_confirmed", "confirm_deal")
+}
+
+
+
+pub fn try_release_deal(
+    deps: DepsMut,
+    env: Env,
+    deal_id: u32,
+) -> Result<Response, ContractError> {
+
+    let deal = DEALS.load(deps.storage, deal_id)?;
+
+    if !deal.deadline.is_expired(&env.block) { return Err(ContractError::DeadlineNotPassed {}); }
+
+    // disputes the arbiter left unresolved return both legs
+    if deal.disputed {
+        return settle(deps.storage, deal_id, deal, 0, deal_refunded(deal_id), "release_deal");
+    }
+
+    release(deps, deal_id, deal, "deal_released", "release_deal")
+}
+
+
+
+pub fn try_dispute_deal(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    deal_id: u32,
+) -> Result<Response, ContractError> {
+
+    let mut deal = DEALS.load(deps.storage, deal_id)?;
+
+    if deal.buyer != *sender && deal.seller != *sender { return Err(ContractError::Unauthorized {}); }
+    if deal.arbiter.is_none() { return Err(ContractError::NoArbiter {}); }
+    if deal.disputed { return Err(ContractError::DealDisputed {}); }
+    if deal.deadline.is_expired(&env.block) { return Err(ContractError::DeadlinePassed {}); }
+
+    deal.disputed = true;
+    deal.deadline = RESOLVE_PERIOD.after(&env.block);
+    DEALS.save(deps.storage, deal_id, &deal)?;
+
+    Ok(Response::new()
+        .add_event(deal_disputed(deal_id, sender))
+        .add_attribute("method", "dispute_deal")
+    )
+}
+
+
+
+pub fn try_resolve_deal(
+    deps: DepsMut,
+    sender: &Addr,
+    deal_id: u32,
+    seller_bps: u16,
+) -> Result<Response, ContractError> {
+
+    let deal = DEALS.load(deps.storage, deal_id)?;
+
+    if deal.arbiter.as_ref() != Some(sender) { return Err(ContractError::Unauthorized {}); }
+    if !deal.disputed { return Err(ContractError::NotDisputed {}); }
+
+    if seller_bps > TOTAL_BPS {
+        return Err(ContractError::InvalidPayoutSplit { reason: "seller share can't be above 10000 bps".to_string() });
+    }
+
+    settle(deps.storage, deal_id, deal, seller_bps, deal_resolved(deal_id, sender, seller_bps), "resolve_deal")
+}
+
+
+
+/// Closes a disputed deal, `seller_bps` of the payment to the seller and the same share
+/// of the sold asset to the buyer, the rest returned
+fn settle(
+    storage: &mut dyn Storage,
+    deal_id: u32,
+    deal: Deal,
+    seller_bps: u16,
+    event: Event,
+    method: &str,
+) -> Result<Response, ContractError> {
+
+    DEALS.remove(storage, deal_id);
+    burn(storage, Claim::Deal(deal_id))?;
+
+    let to_seller = deal.payment.amount.multiply_ratio(seller_bps, TOTAL_BPS);
+    let to_buyer = deal.sell_asset.amount.multiply_ratio(seller_bps, TOTAL_BPS);
+
+    let sends = [
+        (&deal.seller, &deal.payment.denom, to_seller),
+        (&deal.buyer, &deal.payment.denom, deal.payment.amount - to_seller),
+        (&deal.buyer, &deal.sell_asset.denom, to_buyer),
+        (&deal.seller, &deal.sell_asset.denom, deal.sell_asset.amount - to_buyer),
+    ];
+
+    let mut res = Response::new()
+        .add_event(event)
+        .add_attribute("method", method);
+
+    for (recipient, denom, amount) in sends {
+        if !amount.is_zero() {
+            res = res.add_message(send_asset_msg(denom, amount, recipient.as_str())?);
+        }
+    }
+
+    Ok(res)
+}
+
+
+
+pub fn query_deals(
+    deps: Deps,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<GetDealsResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let deals = DEALS
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetDealsResponse { deals })
+}
@@ -0,0 +1,170 @@
+//! Events emitted on otc lifecycle changes, indexed as `wasm-<type>`.
+//!
+//! The attributes below are a stable schema, new ones may be added but none are renamed or removed.
+//! Assets are written as `native:<denom>` or `cw20:<address>`, expiries in the `cw_utils::Expiration` display form.
+//!
+//! - `otc_created`: `otc_id`, `seller`, `sell_asset`, `sell_amount`, `ask_asset`, `ask_amount`, `expires`.
+//!   Amounts are per fill for recurring otcs
+//! - `otc_filled`: `otc_id`, `seller`, `buyer`, `sell_asset`, `sell_amount`, `ask_asset`, `ask_amount`, `fee`, `buyer_fee`.
+//!   Amounts are the part traded in this fill, `fee` is the part of `ask_amount` not paid out to the seller
+//!   and `buyer_fee` the part of `sell_amount` not paid out to the buyer
+//! - `otc_cancelled`: `otc_id`, `seller`, `sell_asset`, `sell_amount` returned to the seller
+//! - `otc_expired`: same attributes as `otc_cancelled` for an escrow returned after the expiry,
+//!   with `reclaimed_by` when closed by someone else than the seller, as in a cleanup of expired otcs
+//! - `deal_opened`: `deal_id`, `otc_id`, `seller`, `buyer`, `sell_asset`, `sell_amount`, `payment_asset`,
+//!   `payment_amount`, `deadline` when an escrow otc is swapped
+//! - `deal_confirmed` and `deal_released`: `deal_id` when the buyer confirms or the deadline passes
+//! - `deal_disputed`: `deal_id`, `by`
+//! - `deal_resolved`: `deal_id`, `arbiter`, `seller_bps`
+//! - `deal_refunded`: `deal_id` when a dispute is left unresolved past its deadline and both legs are returned
+//! - `position_opened`: `position_id`, `otc_id`, `owner`, `asset`, `amount`, `start` when a vesting otc is filled
+//! - `vested_claimed`: `position_id`, `owner`, `amount`, `claimed` in total
+//! - `program_created`: `program_id`, `owner`, `pay_asset`, `escrow`, `buy_asset`, `amount`, `installments`
+//! - `installment_run`: `program_id`, `otc_id` of the filled otc, `spent`, `tip`, `installments_left`
+//! - `program_ended`: `program_id`, `owner`, `refund` of the escrow left when the last installment ran or on cancel
+
+use cosmwasm_std::{Addr, Event, Uint128};
+
+use crate::state::{Deal, OTCInfo, Position, Program};
+use crate::utils::denom_key;
+
+
+
+pub fn otc_created(otc_id: u32, seller: &Addr, otc: &OTCInfo) -> Event {
+    Event::new("otc_created")
+        .add_attribute("otc_id", otc_id.to_string())
+        .add_attribute("seller", seller)
+        .add_attribute("sell_asset", denom_key(&otc.sell_asset()))
+        .add_attribute("sell_amount", otc.sell_amount)
+        .add_attribute("ask_asset", denom_key(&otc.ask_asset()))
+        .add_attribute("ask_amount", otc.ask_amount)
+        .add_attribute("expires", otc.expires.to_string())
+}
+
+
+
+/// Amounts traded in a single fill of an otc
+pub struct Fill {
+    pub sell_amount: Uint128,
+    pub ask_amount: Uint128,
+    pub fee: Uint128,
+    pub buyer_fee: Uint128,
+}
+
+
+pub fn otc_filled(otc_id: u32, otc: &OTCInfo, seller: &Addr, buyer: &Addr, fill: Fill) -> Event {
+    Event::new("otc_filled")
+        .add_attribute("otc_id", otc_id.to_string())
+        .add_attribute("seller", seller)
+        .add_attribute("buyer", buyer)
+        .add_attribute("sell_asset", denom_key(&otc.sell_asset()))
+        .add_attribute("sell_amount", fill.sell_amount)
+        .add_attribute("ask_asset", denom_key(&otc.ask_asset()))
+        .add_attribute("ask_amount", fill.ask_amount)
+        .add_attribute("fee", fill.fee)
+        .add_attribute("buyer_fee", fill.buyer_fee)
+}
+
+
+
+/// `otc_expired` once the otc has expired, `otc_cancelled` before
+pub fn otc_closed(otc_id: u32, otc: &OTCInfo, seller: &Addr, expired: bool) -> Event {
+    Event::new(if expired { "otc_expired" } else { "otc_cancelled" })
+        .add_attribute("otc_id", otc_id.to_string())
+        .add_attribute("seller", seller)
+        .add_attribute("sell_asset", denom_key(&otc.sell_asset()))
+        .add_attribute("sell_amount", otc.escrowed())
+}
+
+
+
+pub fn deal_opened(deal_id: u32, deal: &Deal) -> Event {
+    Event::new("deal_opened")
+        .add_attribute("deal_id", deal_id.to_string())
+        .add_attribute("otc_id", deal.otc_id.to_string())
+        .add_attribute("seller", &deal.seller)
+        .add_attribute("buyer", &deal.buyer)
+        .add_attribute("sell_asset", denom_key(&deal.sell_asset.denom))
+        .add_attribute("sell_amount", deal.sell_asset.amount)
+        .add_attribute("payment_asset", denom_key(&deal.payment.denom))
+        .add_attribute("payment_amount", deal.payment.amount)
+        .add_attribute("deadline", deal.deadline.to_string())
+}
+
+
+
+pub fn deal_disputed(deal_id: u32, by: &Addr) -> Event {
+    Event::new("deal_disputed")
+        .add_attribute("deal_id", deal_id.to_string())
+        .add_attribute("by", by)
+}
+
+
+
+pub fn deal_resolved(deal_id: u32, arbiter: &Addr, seller_bps: u16) -> Event {
+    Event::new("deal_resolved")
+        .add_attribute("deal_id", deal_id.to_string())
+        .add_attribute("arbiter", arbiter)
+        .add_attribute("seller_bps", seller_bps.to_string())
+}
+
+
+
+pub fn deal_refunded(deal_id: u32) -> Event {
+    Event::new("deal_refunded").add_attribute("deal_id", deal_id.to_string())
+}
+
+
+
+pub fn position_opened(position_id: u32, position: &Position) -> Event {
+    Event::new("position_opened")
+        .add_attribute("position_id", position_id.to_string())
+        .add_attribute("otc_id", position.otc_id.to_string())
+        .add_attribute("owner", &position.owner)
+        .add_attribute("asset", denom_key(&position.asset.denom))
+        .add_attribute("amount", position.asset.amount)
+        .add_attribute("start", position.start.to_string())
+}
+
+
+
+pub fn vested_claimed(position_id: u32, position: &Position, amount: Uint128) -> Event {
+    Event::new("vested_claimed")
+        .add_attribute("position_id", position_id.to_string())
+        .add_attribute("owner", &position.owner)
+        .add_attribute("amount", amount)
+        .add_attribute("claimed", position.claimed)
+}
+
+
+
+pub fn program_created(program_id: u32, program: &Program) -> Event {
+    Event::new("program_created")
+        .add_attribute("program_id", program_id.to_string())
+        .add_attribute("owner", &program.owner)
+        .add_attribute("pay_asset", denom_key(&program.escrow.denom))
+        .add_attribute("escrow", program.escrow.amount)
+        .add_attribute("buy_asset", denom_key(&program.buy_asset))
+        .add_attribute("amount", program.amount)
+        .add_attribute("installments", program.installments_left.to_string())
+}
+
+
+
+pub fn installment_run(program_id: u32, program: &Program, otc_id: u32, spent: Uint128) -> Event {
+    Event::new("installment_run")
+        .add_attribute("program_id", program_id.to_string())
+        .add_attribute("otc_id", otc_id.to_string())
+        .add_attribute("spent", spent)
+        .add_attribute("tip", program.tip)
+        .add_attribute("installments_left", program.installments_left.to_string())
+}
+
+
+
+pub fn program_ended(program_id: u32, program: &Program) -> Event {
+    Event::new("program_ended")
+        .add_attribute("program_id", program_id.to_string())
+        .add_attribute("owner", &program.owner)
+        .add_attribute("refund", program.escrow.amount)
+}
@@ -0,0 +1,55 @@
+use cosmwasm_std::{Addr, Deps, DepsMut, QuerierWrapper, Response, Storage};
+use cw4::Cw4Contract;
+
+use crate::error::ContractError;
+use crate::hooks::ensure_admin;
+use crate::state::{OTCInfo, STATE};
+
+
+
+/// Admin only, sets or clears the cw4 group gated otcs check their counterparties against
+pub fn try_set_member_group(
+    deps: DepsMut,
+    sender: &Addr,
+    group: Option<String>,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(&deps, sender)?;
+
+    let mut config = STATE.load(deps.storage)?;
+    config.member_group = group.as_ref().map(|group| deps.api.addr_validate(group)).transpose()?;
+    STATE.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_member_group")
+        .add_attribute("group", group.unwrap_or_default())
+    )
+}
+
+
+
+/// Fails unless `addr` is a member of the configured group with at least `min_weight`
+pub fn check_member(
+    storage: &dyn Storage,
+    querier: &QuerierWrapper,
+    addr: &Addr,
+    min_weight: u64,
+) -> Result<(), ContractError> {
+
+    let group = STATE.load(storage)?.member_group.ok_or(ContractError::NoMemberGroup {})?;
+
+    match Cw4Contract::new(group).is_member(querier, addr, None)? {
+        Some(weight) if weight >= min_weight => Ok(()),
+        _ => Err(ContractError::NotMember { min_weight })
+    }
+}
+
+
+
+/// Counterparty check of a gated otc, passes for otcs open to anyone
+pub fn check_counterparty(deps: Deps, otc: &OTCInfo, counterparty: &Addr) -> Result<(), ContractError> {
+    match otc.min_member_weight {
+        Some(min_weight) => check_member(deps.storage, &deps.querier, counterparty, min_weight),
+        None => Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use cosmwasm_std::{to_binary, Addr, DepsMut, Reply, Response, StdResult, Storage, SubMsg, SubMsgResult, WasmMsg};
+
+use crate::error::ContractError;
+use crate::msg::{OtcHookExecuteMsg, OtcHookMsg};
+use crate::state::{HOOKS, STATE};
+
+pub(crate) const HOOK_REPLY_ID: u64 = 1;
+
+
+
+/// Submessages notifying every registered hook, a failing hook is only reported on reply
+pub fn hook_msgs(storage: &dyn Storage, msg: OtcHookMsg) -> StdResult<Vec<SubMsg>> {
+    let msg = to_binary(&OtcHookExecuteMsg::OtcHook(msg))?;
+    HOOKS.prepare_hooks(storage, |hook| {
+        Ok(SubMsg::reply_on_error(
+            WasmMsg::Execute {
+                contract_addr: hook.into_string(),
+                msg: msg.clone(),
+                funds: vec!()
+            },
+            HOOK_REPLY_ID
+        ))
+    })
+}
+
+
+
+pub(crate) fn ensure_admin(deps: &DepsMut, sender: &Addr) -> Result<(), ContractError> {
+    let config = STATE.load(deps.storage)?;
+    if deps.api.addr_canonicalize(sender.as_str())? != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+
+
+pub fn try_add_hook(
+    deps: DepsMut,
+    sender: &Addr,
+    addr: String,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(&deps, sender)?;
+
+    let hook = deps.api.addr_validate(&addr)?;
+    HOOKS.add_hook(deps.storage, hook)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "add_hook")
+        .add_attribute("hook", addr)
+    )
+}
+
+
+
+pub fn try_remove_hook(
+    deps: DepsMut,
+    sender: &Addr,
+    addr: String,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(&deps, sender)?;
+
+    let hook = deps.api.addr_validate(&addr)?;
+    HOOKS.remove_hook(deps.storage, hook)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "remove_hook")
+        .add_attribute("hook", addr)
+    )
+}
+
+
+
+/// Swallows the error of a failed hook so it can't revert the trade
+pub fn hook_reply(msg: Reply) -> Result<Response, ContractError> {
+    let error = match msg.result {
+        SubMsgResult::Err(error) => error,
+        SubMsgResult::Ok(_) => String::new()
+    };
+
+    Ok(Response::new()
+        .add_attribute("method", "hook_failed")
+        .add_attribute("error", error)
+    )
+}
@@ -1,6 +1,22 @@
 pub mod error;
+pub mod escrow;
+pub mod events;
+pub mod gating;
+pub mod auction;
+pub mod batch;
+pub mod book;
+pub mod compliance;
 pub mod contract;
+pub mod dca;
+pub mod hooks;
+pub mod listing;
+pub mod matching;
 pub mod msg;
+pub mod nft;
+pub mod oracle;
+pub mod referral;
+pub mod rfq;
 pub mod state;
 pub mod utils;
+pub mod vesting;
 pub mod tests;
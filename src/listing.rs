@@ -0,0 +1,252 @@
+use cosmwasm_std::{Addr, BankMsg, BlockInfo, CanonicalAddr, Coin, CosmosMsg, Deps, DepsMut, Env, Order, Response, StdResult, Storage, Uint128};
+use cw_storage_plus::Bound;
+
+use crate::batch::merge;
+use crate::contract::{try_cancel, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::hooks::ensure_admin;
+use crate::msg::{ExpiredCountResponse, ListingCreditResponse, ListingRulesResponse};
+use crate::state::{Asset, OTCInfo, EXPIRY_INDEX, LISTING_CREDITS, MIN_NOTIONAL, OPEN_OTCS, STATE};
+use crate::utils::denom_key;
+
+
+
+/// Admin only, replaces the deposit and the open otc limit and updates the minimums per asset,
+/// a zero minimum removes it
+pub fn try_update_listing_rules(
+    deps: DepsMut,
+    sender: &Addr,
+    deposit: Option<Coin>,
+    max_open_otcs: Option<u32>,
+    min_notional: Vec<Asset>,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(&deps, sender)?;
+
+    let mut config = STATE.load(deps.storage)?;
+    config.listing_deposit = deposit.filter(|deposit| !deposit.amount.is_zero());
+    config.max_open_otcs = max_open_otcs;
+    STATE.save(deps.storage, &config)?;
+
+    for Asset { denom, amount } in min_notional {
+        if amount.is_zero() {
+            MIN_NOTIONAL.remove(deps.storage, &denom_key(&denom));
+        } else {
+            MIN_NOTIONAL.save(deps.storage, &denom_key(&denom), &Asset { denom, amount })?;
+        }
+    }
+
+    Ok(Response::new().add_attribute("method", "update_listing_rules"))
+}
+
+
+
+/// Takes the deposit of `count` listings out of native funds, returning the rest and the deposit of one
+pub fn split_deposit(
+    storage: &dyn Storage,
+    mut funds: Vec<Coin>,
+    count: usize,
+) -> Result<(Vec<Coin>, Option<Coin>), ContractError> {
+
+    let deposit = match STATE.load(storage)?.listing_deposit {
+        Some(deposit) => deposit,
+        None => return Ok((funds, None))
+    };
+
+    let needed = deposit.amount * Uint128::from(count as u128);
+
+    let paid = funds
+        .iter_mut()
+        .find(|coin| coin.denom == deposit.denom && coin.amount >= needed)
+        .ok_or_else(|| ContractError::DepositMissing { deposit: deposit.clone() })?;
+
+    paid.amount -= needed;
+    funds.retain(|coin| !coin.amount.is_zero());
+
+    Ok((funds, Some(deposit)))
+}
+
+
+
+/// Takes the deposit of a listing out of the prepaid credit of the seller
+pub fn take_credit(storage: &mut dyn Storage, seller: &Addr) -> Result<Option<Coin>, ContractError> {
+
+    let deposit = match STATE.load(storage)?.listing_deposit {
+        Some(deposit) => deposit,
+        None => return Ok(None)
+    };
+
+    let mut credit = LISTING_CREDITS
+        .may_load(storage, seller)?
+        .filter(|credit| credit.denom == deposit.denom && credit.amount >= deposit.amount)
+        .ok_or_else(|| ContractError::DepositMissing { deposit: deposit.clone() })?;
+
+    credit.amount -= deposit.amount;
+    LISTING_CREDITS.save(storage, seller, &credit)?;
+
+    Ok(Some(deposit))
+}
+
+
+
+/// Prepays deposits for listings created from cw20 sends, which can't carry native funds
+pub fn try_top_up_credit(
+    deps: DepsMut,
+    sender: &Addr,
+    funds: Vec<Coin>,
+) -> Result<Response, ContractError> {
+
+    let deposit = STATE.load(deps.storage)?.listing_deposit.ok_or(ContractError::NoDeposit {})?;
+
+    let paid = match funds.as_slice() {
+        [coin] if coin.denom == deposit.denom && !coin.amount.is_zero() => coin.amount,
+        _ => return Err(ContractError::DepositMissing { deposit })
+    };
+
+    // credit left in a previous deposit denom is given back
+    let mut refund = None;
+
+    let credit = LISTING_CREDITS.update(deps.storage, sender, |credit| -> StdResult<_> {
+        Ok(match credit {
+            Some(credit) if credit.denom == deposit.denom => Coin { amount: credit.amount + paid, ..credit },
+            other => {
+                refund = other.filter(|credit| !credit.amount.is_zero());
+                Coin { denom: deposit.denom.clone(), amount: paid }
+            }
+        })
+    })?;
+
+    Ok(Response::new()
+        .add_messages(refund.map(|refund| BankMsg::Send { to_address: sender.to_string(), amount: vec!(refund) }))
+        .add_attribute("method", "top_up_credit")
+        .add_attribute("credit", credit.to_string())
+    )
+}
+
+
+
+pub fn try_withdraw_credit(
+    deps: DepsMut,
+    sender: &Addr,
+) -> Result<Response, ContractError> {
+
+    let credit = LISTING_CREDITS
+        .may_load(deps.storage, sender)?
+        .filter(|credit| !credit.amount.is_zero())
+        .ok_or(ContractError::NothingToClaim {})?;
+
+    LISTING_CREDITS.remove(deps.storage, sender);
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send { to_address: sender.to_string(), amount: vec!(credit.clone()) })
+        .add_attribute("method", "withdraw_credit")
+        .add_attribute("amount", credit.to_string())
+    )
+}
+
+
+
+/// Returns the listing deposit of a closed otc
+pub fn deposit_msg(otc: &OTCInfo, recipient: &str) -> Option<CosmosMsg> {
+    otc.deposit.clone().map(|deposit| BankMsg::Send { to_address: recipient.to_string(), amount: vec!(deposit) }.into())
+}
+
+
+
+/// Limits on open otcs per seller and on the amounts traded of each asset
+pub fn check_listing(storage: &dyn Storage, seller: &CanonicalAddr, otc: &OTCInfo) -> Result<(), ContractError> {
+
+    if let Some(max) = STATE.load(storage)?.max_open_otcs {
+        if OPEN_OTCS.may_load(storage, seller.as_slice())?.unwrap_or_default() >= max {
+            return Err(ContractError::TooManyOpenOtcs { max });
+        }
+    }
+
+    for (asset, amount) in [(otc.sell_asset(), otc.sell_amount), (otc.ask_asset(), otc.ask_amount)] {
+        if let Some(min) = MIN_NOTIONAL.may_load(storage, &denom_key(&asset))? {
+            if amount < min.amount {
+                return Err(ContractError::BelowMinNotional { asset: denom_key(&asset), min: min.amount });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+
+/// Ids of the expired otcs, those expiring at a height first, in expiry order
+fn expired_ids<'a>(storage: &'a dyn Storage, block: &BlockInfo) -> impl Iterator<Item = StdResult<u32>> + 'a {
+    let expired = |kind: u8, now: u64| EXPIRY_INDEX
+        .sub_prefix(kind)
+        .keys(storage, None, Some(Bound::inclusive((now, u32::MAX))), Order::Ascending)
+        .map(|key| key.map(|(_, id)| id));
+
+    expired(0, block.height).chain(expired(1, block.time.nanos()))
+}
+
+
+
+/// Permissionless, closes up to `limit` expired otcs returning the escrows to the sellers,
+/// the sender keeps their listing deposits as a bounty
+pub fn try_cleanup_expired(
+    mut deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let otc_ids = expired_ids(deps.storage, &env.block)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    if otc_ids.is_empty() { return Err(ContractError::NothingExpired {}); }
+
+    let mut res = Response::new().add_attribute("method", "cleanup_expired");
+
+    for otc_id in &otc_ids {
+        res = merge(res, try_cancel(deps.branch(), env.clone(), sender, *otc_id)?);
+    }
+
+    Ok(res.add_attribute("closed", otc_ids.len().to_string()))
+}
+
+
+
+pub fn query_listing_rules(deps: Deps) -> StdResult<ListingRulesResponse> {
+    let config = STATE.load(deps.storage)?;
+
+    let min_notional = MIN_NOTIONAL
+        .range(deps.storage, None, None, Order::Ascending)
+        .take(MAX_LIMIT as usize)
+        .map(|min| min.map(|(_, min)| min))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(ListingRulesResponse {
+        deposit: config.listing_deposit,
+        max_open_otcs: config.max_open_otcs,
+        min_notional,
+    })
+}
+
+
+
+/// Counts up to the largest cleanup batch
+pub fn query_expired_count(deps: Deps, env: Env) -> StdResult<ExpiredCountResponse> {
+    // counts one past the cap to tell whether more are waiting
+    let count = expired_ids(deps.storage, &env.block).take(MAX_LIMIT as usize + 1).count() as u32;
+    Ok(ExpiredCountResponse { 
+        count: count.min(MAX_LIMIT), 
+        capped: count > MAX_LIMIT 
+    })
+}
+
+
+
+pub fn query_listing_credit(deps: Deps, address: String) -> StdResult<ListingCreditResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let credit = LISTING_CREDITS.may_load(deps.storage, &address)?;
+    Ok(ListingCreditResponse { credit })
+}
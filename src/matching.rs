@@ -0,0 +1,202 @@
+use cosmwasm_std::{Api, CosmosMsg, DepsMut, Env, Response, StdError, StdResult, Storage, Uint128, Uint256};
+
+use crate::batch::merge;
+use crate::compliance::{check_allowed_addresses, check_allowed_assets};
+use crate::error::ContractError;
+use crate::events::{otc_filled, Fill};
+use crate::hooks::hook_msgs;
+use crate::listing::deposit_msg;
+use crate::msg::{MatchPair, OtcHookMsg};
+use crate::oracle::check_condition;
+use crate::referral::{record_referral, referral_fee};
+use crate::state::{OTCInfo, PriceImprovement, OTCS, STATE, save_otc, remove_otc};
+use crate::utils::send_asset_msg;
+
+
+
+/// `amount * numerator / denominator` rounded up
+fn multiply_ratio_ceil(amount: Uint128, numerator: Uint128, denominator: Uint128) -> StdResult<Uint128> {
+    let denominator = Uint256::from(denominator);
+    let result = (amount.full_mul(numerator) + denominator - Uint256::from(1u8)) / denominator;
+    Uint128::try_from(result).map_err(|e| StdError::generic_err(e.to_string()))
+}
+
+
+
+/// Saves the remainder of a partially filled otc or removes it once nothing is left to trade
+fn store_remaining(
+    api: &dyn Api,
+    storage: &mut dyn Storage,
+    otc_id: u32,
+    otc: &OTCInfo,
+    messages: &mut Vec<CosmosMsg>,
+) -> StdResult<()> {
+    let seller = api.addr_humanize(&otc.seller)?;
+
+    if otc.sell_amount.is_zero() {
+        messages.extend(deposit_msg(otc, seller.as_str()));
+        remove_otc(storage, otc_id)?;
+    } else if otc.ask_amount.is_zero() {
+        messages.push(send_asset_msg(&otc.sell_asset(), otc.sell_amount, seller.as_str())?);
+        messages.extend(deposit_msg(otc, seller.as_str()));
+        remove_otc(storage, otc_id)?;
+    } else {
+        save_otc(storage, otc_id, otc)?;
+    }
+    Ok(())
+}
+
+
+
+fn match_otcs(
+    deps: DepsMut,
+    env: &Env,
+    maker_id: u32,
+    taker_id: u32,
+) -> Result<Response, ContractError> {
+
+    if maker_id == taker_id {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "Can't match an otc with itself".to_string()
+            }
+        ));
+    }
+
+    let config = STATE.load(deps.storage)?;
+
+    let mut maker = OTCS.load(deps.storage, maker_id)?;
+    let mut taker = OTCS.load(deps.storage, taker_id)?;
+
+    for otc in [&maker, &taker] {
+        if otc.expires.is_expired(&env.block) { return Err(ContractError::OtcExpired {}); }
+        if !otc.matchable() { return Err(ContractError::NotMatchable {}); }
+        check_condition(&deps.querier, env, otc)?;
+    }
+
+    if maker.sell_asset() != taker.ask_asset() || maker.ask_asset() != taker.sell_asset() {
+        return Err(ContractError::WrongDenom {});
+    }
+
+    // the taker has to give at least as much per unit as the maker asks
+    if taker.sell_amount.full_mul(maker.sell_amount) < maker.ask_amount.full_mul(taker.ask_amount) {
+        return Err(ContractError::NotCrossing {});
+    }
+
+    let traded = maker.sell_amount.min(taker.ask_amount);
+
+    let maker_gets = multiply_ratio_ceil(traded, maker.ask_amount, maker.sell_amount)?;
+
+    // what the taker gives up at its own price, so that any remainder keeps that price
+    let taker_spent = traded
+        .multiply_ratio(taker.sell_amount, taker.ask_amount)
+        .max(maker_gets);
+
+    let taker_pays = match &config.price_improvement {
+        PriceImprovement::Taker => maker_gets,
+        PriceImprovement::Collector(_) => taker_spent
+    };
+
+    let maker_seller = deps.api.addr_humanize(&maker.seller)?;
+    let taker_seller = deps.api.addr_humanize(&taker.seller)?;
+
+    check_allowed_addresses(
+        deps.storage,
+        [&maker_seller, &taker_seller].into_iter().chain(maker.payees()).chain(taker.payees())
+    )?;
+    check_allowed_assets(deps.storage, [&maker.sell_asset(), &maker.ask_asset()])?;
+
+    // each side pays its own referrer out of what it receives
+    let maker_fee = referral_fee(config.referral_fee, maker.referrer.as_ref(), maker_gets);
+    let taker_fee = referral_fee(config.referral_fee, taker.referrer.as_ref(), traded);
+
+    let mut messages = taker.payout_msgs(deps.api, &maker.sell_asset(), traded - taker_fee)?;
+    messages.extend(maker.payout_msgs(deps.api, &maker.ask_asset(), maker_gets - maker_fee)?);
+
+    for (otc, fee) in [(&maker, maker_fee), (&taker, taker_fee)] {
+        if let Some(referrer) = otc.referrer.as_ref().filter(|_| !fee.is_zero()) {
+            messages.push(send_asset_msg(&otc.ask_asset(), fee, referrer.as_str())?);
+            record_referral(deps.storage, referrer, &otc.ask_asset(), fee)?;
+        }
+    }
+
+    if taker_spent > maker_gets {
+        let improvement_to = match &config.price_improvement {
+            PriceImprovement::Taker => &taker_seller,
+            PriceImprovement::Collector(collector) => collector
+        };
+        messages.push(send_asset_msg(&maker.ask_asset(), taker_spent - maker_gets, improvement_to.as_str())?);
+    }
+
+    maker.sell_amount -= traded;
+    maker.ask_amount -= maker_gets;
+    taker.ask_amount -= traded;
+    taker.sell_amount -= taker_spent;
+
+    store_remaining(deps.api, deps.storage, maker_id, &maker, &mut messages)?;
+    store_remaining(deps.api, deps.storage, taker_id, &taker, &mut messages)?;
+
+    let mut hooks = hook_msgs(deps.storage, OtcHookMsg::Filled {
+        otc_id: maker_id,
+        buyer: taker_seller.to_string(),
+        sell_amount: traded,
+        ask_amount: maker_gets
+    })?;
+    hooks.extend(hook_msgs(deps.storage, OtcHookMsg::Filled {
+        otc_id: taker_id,
+        buyer: maker_seller.to_string(),
+        sell_amount: taker_pays,
+        ask_amount: traded
+    })?);
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_submessages(hooks)
+        .add_event(otc_filled(maker_id, &maker, &maker_seller, &taker_seller, Fill {
+            sell_amount: traded,
+            ask_amount: maker_gets,
+            fee: maker_fee,
+            buyer_fee: taker_fee,
+        }))
+        .add_event(otc_filled(taker_id, &taker, &taker_seller, &maker_seller, Fill {
+            sell_amount: taker_pays,
+            ask_amount: traded,
+            fee: taker_fee,
+            buyer_fee: maker_fee,
+        }))
+        .add_attribute("method", "match")
+        .add_attribute("maker_id", maker_id.to_string())
+        .add_attribute("taker_id", taker_id.to_string())
+        .add_attribute("traded", traded)
+    )
+}
+
+
+
+pub fn try_match(
+    deps: DepsMut,
+    env: Env,
+    maker_id: u32,
+    taker_id: u32,
+) -> Result<Response, ContractError> {
+    match_otcs(deps, &env, maker_id, taker_id)
+}
+
+
+
+pub fn try_batch_match(
+    mut deps: DepsMut,
+    env: Env,
+    matches: Vec<MatchPair>,
+) -> Result<Response, ContractError> {
+
+    let mut res = Response::new().add_attribute("method", "batch_match");
+
+    for MatchPair { maker_id, taker_id } in matches {
+        let matched = match_otcs(deps.branch(), &env, maker_id, taker_id)?;
+        res = res.add_attributes(matched.attributes.iter().filter(|attr| attr.key != "method").cloned());
+        res = merge(res, matched);
+    }
+
+    Ok(res)
+}
@@ -1,9 +1,10 @@
-use cw_utils::Expiration;
+use cosmwasm_std::{Addr, Binary, Coin, CosmosMsg, Decimal, Decimal256, Timestamp, Uint128};
+use cw_utils::{Duration, Expiration};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cw20::{Balance, Cw20ReceiveMsg};
+use cw20::{Balance, Cw20ReceiveMsg, Denom};
 
-use crate::state::{UserInfo, OTCInfo};
+use crate::state::{UserInfo, OTCInfo, PayoutShare, PriceAdjustment, Condition, Escrow, Deal, VestingSchedule, Position, Program, DutchAuction, AuctionInfo, SealedBidding, SealedBid, Asset, RfqInfo, Quote, PriceImprovement};
 
 
 
@@ -12,7 +13,7 @@ pub struct InstantiateMsg {
 }
 
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
 pub struct NewOTC {
     pub ask_balance: Balance, 
 
@@ -23,34 +24,407 @@ pub struct NewOTC {
     pub user_info: Option<UserInfo>,
 
     // optional description
-    pub description: Option<String>
+    pub description: Option<String>,
+
+    // optional decaying ask starting from the amount of ask_balance
+    pub dutch_auction: Option<DutchAuction>,
+
+    // optional receiver of the proceeds, the seller by default
+    pub recipient: Option<String>,
+
+    // optional message the proceeds are sent to the recipient contract with
+    pub payout_msg: Option<Binary>,
+
+    // optional frontend or broker paid the referral fee out of the proceeds
+    pub referrer: Option<String>,
+
+    // optional split of the proceeds summing up to 10000 bps,
+    // can't be combined with recipient and payout_msg
+    pub payout_split: Option<Vec<PayoutShare>>,
+
+    // optional ask following an oracle price of the sold asset in the ask asset,
+    // the amount of ask_balance is not used then
+    pub pegged_price: Option<NewPeggedPrice>,
+
+    // optional condition that must hold for the otc to be filled
+    pub condition: Option<Condition>,
+
+    // optional escrow holding swaps until the buyer confirms an off-chain leg
+    pub escrow: Option<Escrow>,
+
+    // optional vesting of the sold asset to the buyer, can't be combined with escrow
+    pub vesting: Option<VestingSchedule>,
+
+    // optional number of fills of a recurring otc, the sent amount is
+    // the total escrow split evenly between them
+    pub repeat: Option<u32>,
+
+    // optional minimum weight in the configured cw4 group the buyer and the
+    // recipient must have, 0 for any member, also required of new owners of
+    // the deal or position token of a fill
+    pub min_member_weight: Option<u64>,
 }
 
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewPeggedPrice {
+    pub oracle: String,
+    pub adjustment: Option<PriceAdjustment>,
+    // oldest accepted feed update in seconds
+    pub max_staleness: u64,
+    // largest accepted relative move of the feed away from the price at creation
+    pub max_deviation: Option<Decimal>,
+}
 
 
+/// Query interface the oracle of pegged otcs has to implement
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+pub enum OracleQueryMsg {
+    /// Amount of `quote` per unit of `base`
+    Price {
+        base: Denom,
+        quote: Denom
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OraclePriceResponse {
+    pub price: Decimal,
+    pub updated_at: Timestamp,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BatchNewOTC {
+    // part of the sent funds escrowed by this otc
+    pub sell: Coin,
+    pub otc: NewOTC,
+}
+
+
+/// Installments of the sent amount buying `buy_asset` from the cheapest fitting otc,
+/// the sent amount must be `(amount + tip) * installments`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewProgram {
+    pub buy_asset: Denom,
+
+    // most of the sent asset spent on a single installment
+    pub amount: Uint128,
+
+    // highest accepted price in the sent asset per unit bought
+    pub max_price: Decimal256,
+
+    // time between installments, the first one is due right away
+    pub interval: Duration,
+
+    pub installments: u32,
+
+    // paid to the cranker for every installment that fills an otc
+    pub tip: Uint128,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewAuction {
+    // asset the bids are made in
+    pub bid_asset: Denom,
+
+    pub reserve_price: Uint128,
+
+    pub min_increment: Uint128,
+
+    pub end: Expiration,
+
+    // anti-sniping window, must be of the same kind as end
+    pub extension: Option<Duration>,
+
+    pub user_info: Option<UserInfo>,
+
+    pub description: Option<String>,
+
+    // makes bids sealed, min_increment and extension are not used then
+    // and each commitment must deposit at least the reserve price
+    pub sealed: Option<SealedBidding>,
+}
+
+
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     Create(NewOTC),
 
     Swap {
+        otc_id: u32,
+        // receiver of the sold asset, the sender by default
+        recipient: Option<String>,
+        // cw20 send or wasm execute message for the recipient contract
+        payout_msg: Option<Binary>,
+        // paid the referral fee out of the sold asset
+        referrer: Option<String>
+    },
+
+    /// Seller only, returns the escrow of the otc, expired otcs can be
+    /// reclaimed by anyone who then keeps the listing deposit
+    Cancel {
         otc_id: u32
     },
 
+    /// Sent funds must add up to the sell amounts of the entries
+    BatchCreate {
+        otcs: Vec<BatchNewOTC>
+    },
+
+    /// Sent funds are split by the current asks, the rest is refunded
+    BatchSwap {
+        otc_ids: Vec<u32>
+    },
+
+    BatchCancel {
+        otc_ids: Vec<u32>
+    },
+
+    CreateAuction(NewAuction),
+
+    Bid {
+        auction_id: u32
+    },
+
+    /// Deposit must cover the bid that is revealed later
+    CommitBid {
+        auction_id: u32,
+        commitment: Binary
+    },
+
+    RevealBid {
+        auction_id: u32,
+        amount: Uint128,
+        salt: Binary
+    },
+
+    /// Can be called by anyone once the auction has ended
+    Settle {
+        auction_id: u32
+    },
+
+    CreateRfq {
+        want: Asset,
+        pay_asset: Denom,
+        expires: Option<Expiration>
+    },
+
+    /// Requester only, refunds all quotes
+    CancelRfq {
+        rfq_id: u32
+    },
+
+    /// Funds must be exactly the wanted asset of the rfq
+    Quote {
+        rfq_id: u32,
+        pay_amount: Uint128
+    },
+
+    WithdrawQuote {
+        rfq_id: u32
+    },
+
+    /// Requester pays the quoted amount and the other quotes are refunded
+    AcceptQuote {
+        rfq_id: u32,
+        maker: String
+    },
+
+    /// Owner of the deal token only, releases the settlement of the deal
+    ConfirmDeal {
+        deal_id: u32
+    },
+
+    /// Can be called by anyone once the deadline has passed, releases the deal without
+    /// a dispute and refunds both legs of a dispute the arbiter didn't resolve in time
+    ReleaseDeal {
+        deal_id: u32
+    },
+
+    /// Owner of the deal token or seller, before the deadline, which moves to the
+    /// end of the 30 day period the arbiter has to resolve it
+    DisputeDeal {
+        deal_id: u32
+    },
+
+    /// Arbiter only, `seller_bps` of the payment goes to the seller and the
+    /// same share of the sold asset to the buyer, the rest is returned
+    ResolveDeal {
+        deal_id: u32,
+        seller_bps: u16
+    },
+
+    /// Owner of the position token only, sends the vested part not claimed yet
+    ClaimVested {
+        position_id: u32
+    },
+
+    /// Moves a position or deal token, the claim goes along with it
+    TransferNft {
+        recipient: String,
+        token_id: String
+    },
+
+    /// Moves a token to a contract and notifies it with a `Cw721ReceiveMsg`
+    SendNft {
+        contract: String,
+        token_id: String,
+        msg: Binary
+    },
+
+    /// Allows the spender to transfer or send the token
+    Approve {
+        spender: String,
+        token_id: String,
+        expires: Option<Expiration>
+    },
+
+    Revoke {
+        spender: String,
+        token_id: String
+    },
+
+    /// Allows the operator to transfer or send any token of the sender
+    ApproveAll {
+        operator: String,
+        expires: Option<Expiration>
+    },
+
+    RevokeAll {
+        operator: String
+    },
+
+    CreateProgram(NewProgram),
+
+    /// Owner only, refunds the escrow left
+    CancelProgram {
+        program_id: u32
+    },
+
+    /// Runs the due installments of the programs an otc can fill and pays the tips to the sender
+    Crank {
+        program_ids: Vec<u32>
+    },
+
+    /// Settles two crossing otcs against each other at the maker price
+    Match {
+        maker_id: u32,
+        taker_id: u32
+    },
+
+    BatchMatch {
+        matches: Vec<MatchPair>
+    },
+
+    /// Admin only, registers a contract notified on otc lifecycle events
+    AddHook {
+        addr: String
+    },
+
+    /// Admin only
+    RemoveHook {
+        addr: String
+    },
+
+    /// Admin only, cw4 group gated otcs check their counterparties against, none to clear
+    SetMemberGroup {
+        group: Option<String>
+    },
+
+    /// Admin only, addresses blocked from trading
+    UpdateDenyList {
+        add: Vec<String>,
+        remove: Vec<String>
+    },
+
+    /// Admin only, assets that can be traded once the list is enforced
+    UpdateAllowList {
+        add: Vec<Denom>,
+        remove: Vec<Denom>,
+        enforce: Option<bool>
+    },
+
+    /// Admin only, replaces the listing deposit and the open otc limit, none disables them,
+    /// and sets the minimum amount of each given asset, zero removes it
+    UpdateListingRules {
+        deposit: Option<Coin>,
+        max_open_otcs: Option<u32>,
+        min_notional: Vec<Asset>
+    },
+
+    /// Prepays listing deposits for otcs created from cw20 sends
+    TopUpCredit {},
+
+    /// Returns the listing credit not used yet
+    WithdrawCredit {},
+
+    /// Closes expired otcs in expiry order, returning the escrows to the sellers
+    /// and paying their listing deposits to the sender, which also prunes them from the price book
+    CleanupExpired {
+        limit: Option<u32>
+    },
+
+    /// Admin only
+    UpdateConfig {
+        active: Option<bool>,
+        price_improvement: Option<PriceImprovement>,
+        referral_fee: Option<Decimal>,
+    },
+
     /// This accepts a properly-encoded ReceiveMsg from a cw20 contract
     Receive(Cw20ReceiveMsg),
 }
 
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MatchPair {
+    pub maker_id: u32,
+    pub taker_id: u32,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ReceiveMsg {
     
     Create(NewOTC),
 
     Swap {
-        otc_id: u32
-    }
+        otc_id: u32,
+        recipient: Option<String>,
+        payout_msg: Option<Binary>,
+        referrer: Option<String>
+    },
+
+    CreateAuction(NewAuction),
+
+    CreateProgram(NewProgram),
+
+    Bid {
+        auction_id: u32
+    },
+
+    CommitBid {
+        auction_id: u32,
+        commitment: Binary
+    },
+
+    Quote {
+        rfq_id: u32,
+        pay_amount: Uint128
+    },
+
+    AcceptQuote {
+        rfq_id: u32,
+        maker: String
+    },
 }
 
 
@@ -58,11 +432,236 @@ pub enum ReceiveMsg {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
+    GetConfig {},
+
     GetOtcs { 
         include_expired: Option<bool>,
         start_after: Option<u32>,
         limit: Option<u32>
     },
+
+    CurrentPrice {
+        otc_id: u32
+    },
+
+    /// Whether the otc can be filled at the current block and why not
+    Fillable {
+        otc_id: u32
+    },
+
+    /// Outcome of a swap by `sender` at the current block without executing it
+    SimulateSwap {
+        otc_id: u32,
+        sender: String,
+        offer_asset: Asset,
+        recipient: Option<String>,
+        payout_msg: Option<Binary>,
+        referrer: Option<String>
+    },
+
+    GetAuction {
+        auction_id: u32
+    },
+
+    GetAuctions {
+        include_ended: Option<bool>,
+        start_after: Option<u32>,
+        limit: Option<u32>
+    },
+
+    GetSealedBids {
+        auction_id: u32,
+        start_after: Option<String>,
+        limit: Option<u32>
+    },
+
+    GetDeal {
+        deal_id: u32
+    },
+
+    GetDeals {
+        start_after: Option<u32>,
+        limit: Option<u32>
+    },
+
+    GetPosition {
+        position_id: u32
+    },
+
+    /// Vesting positions, of `owner` only if given
+    GetPositions {
+        owner: Option<String>,
+        start_after: Option<u32>,
+        limit: Option<u32>
+    },
+
+    GetProgram {
+        program_id: u32
+    },
+
+    /// Scheduled buy programs, of `owner` only and with an installment due only if given
+    GetPrograms {
+        owner: Option<String>,
+        due_only: Option<bool>,
+        start_after: Option<u32>,
+        limit: Option<u32>
+    },
+
+    /// Open fixed price otcs selling `sell_asset` for `ask_asset`, cheapest first,
+    /// `start_after` is the price and id of the last otc of the previous page
+    PairBook {
+        sell_asset: Denom,
+        ask_asset: Denom,
+        start_after: Option<(Decimal256, u32)>,
+        limit: Option<u32>
+    },
+
+    /// Pairs with open otcs ordered by asset keys, expired otcs count until they're closed
+    Pairs {
+        start_after: Option<(Denom, Denom)>,
+        limit: Option<u32>
+    },
+
+    /// Cheapest open fixed price otc of the pair
+    BestOffer {
+        sell_asset: Denom,
+        ask_asset: Denom
+    },
+
+    /// Open fixed price otcs of the pair within a price range, cheapest first
+    OffersByPrice {
+        sell_asset: Denom,
+        ask_asset: Denom,
+        min_price: Option<Decimal256>,
+        max_price: Option<Decimal256>,
+        limit: Option<u32>
+    },
+
+    GetRfqs {
+        include_expired: Option<bool>,
+        start_after: Option<u32>,
+        limit: Option<u32>
+    },
+
+    GetQuotes {
+        rfq_id: u32,
+        start_after: Option<String>,
+        limit: Option<u32>
+    },
+
+    /// Referral fees paid out to `address` so far
+    ReferralEarnings {
+        address: String
+    },
+
+    /// Contracts notified on otc lifecycle events
+    Hooks {},
+
+    ListingRules {},
+
+    ListingCredit {
+        address: String
+    },
+
+    /// Number of expired otcs waiting to be cleaned up, capped at the largest cleanup limit
+    /// with `capped` set when more are waiting
+    ExpiredCount {},
+
+    DenyList {
+        start_after: Option<String>,
+        limit: Option<u32>
+    },
+
+    AllowList {
+        start_after: Option<Denom>,
+        limit: Option<u32>
+    },
+
+    /// cw721 queries of the position and deal tokens
+    OwnerOf {
+        token_id: String,
+        include_expired: Option<bool>
+    },
+
+    Approval {
+        token_id: String,
+        spender: String,
+        include_expired: Option<bool>
+    },
+
+    Approvals {
+        token_id: String,
+        include_expired: Option<bool>
+    },
+
+    Operator {
+        owner: String,
+        operator: String,
+        include_expired: Option<bool>
+    },
+
+    AllOperators {
+        owner: String,
+        include_expired: Option<bool>,
+        start_after: Option<String>,
+        limit: Option<u32>
+    },
+
+    NumTokens {},
+
+    ContractInfo {},
+
+    NftInfo {
+        token_id: String
+    },
+
+    AllNftInfo {
+        token_id: String,
+        include_expired: Option<bool>
+    },
+
+    Tokens {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>
+    },
+
+    AllTokens {
+        start_after: Option<String>,
+        limit: Option<u32>
+    },
+}
+
+
+
+/// Otc lifecycle event sent to every registered hook
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum OtcHookMsg {
+    Created {
+        otc_id: u32,
+        otc: OTCInfo
+    },
+
+    /// Partial fills of matched otcs are reported as well
+    Filled {
+        otc_id: u32,
+        buyer: String,
+        sell_amount: Uint128,
+        ask_amount: Uint128
+    },
+
+    Cancelled {
+        otc_id: u32
+    },
+}
+
+/// Execute message the hook contracts have to accept
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OtcHookExecuteMsg {
+    OtcHook(OtcHookMsg),
 }
 
 
@@ -78,4 +677,164 @@ pub struct GetOTCsResponse {
 pub struct NewOTCResponse {
     pub id: u32,
     pub otc: OTCInfo,
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CurrentPriceResponse {
+    pub sell_amount: Uint128,
+    pub ask_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FillableResponse {
+    pub fillable: bool,
+    // error a swap would fail with before the amounts are checked
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetDealsResponse {
+    pub deals: Vec<(u32, Deal)>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PositionResponse {
+    pub id: u32,
+    pub position: Position,
+    pub vested: Uint128,
+    pub claimable: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetPositionsResponse {
+    pub positions: Vec<PositionResponse>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListingRulesResponse {
+    pub deposit: Option<Coin>,
+    pub max_open_otcs: Option<u32>,
+    pub min_notional: Vec<Asset>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListingCreditResponse {
+    pub credit: Option<Coin>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExpiredCountResponse {
+    pub count: u32,
+    // more expired otcs are waiting than counted
+    pub capped: bool
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DenyListResponse {
+    pub addresses: Vec<Addr>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowListResponse {
+    pub enforced: bool,
+    pub assets: Vec<Denom>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetProgramsResponse {
+    pub programs: Vec<(u32, Program)>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetAuctionsResponse {
+    pub auctions: Vec<(u32, AuctionInfo)>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewAuctionResponse {
+    pub id: u32,
+    pub auction: AuctionInfo,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetSealedBidsResponse {
+    pub bids: Vec<(String, SealedBid)>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetRfqsResponse {
+    pub rfqs: Vec<(u32, RfqInfo)>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetQuotesResponse {
+    pub quotes: Vec<(String, Quote)>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceLevel {
+    // ask amount per unit sold
+    pub price: Decimal256,
+    pub sell_amount: Uint128,
+    pub ask_amount: Uint128,
+    pub count: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PairBookResponse {
+    pub otcs: Vec<(u32, OTCInfo)>,
+    pub levels: Vec<PriceLevel>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PairInfo {
+    pub sell_asset: Denom,
+    pub ask_asset: Denom,
+    pub count: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PairsResponse {
+    pub pairs: Vec<PairInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BestOfferResponse {
+    pub offer: Option<(u32, OTCInfo)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateSwapResponse {
+    // error the swap would fail with, amounts are zero then
+    pub error: Option<String>,
+    // paid for the otc
+    pub ask_amount: Uint128,
+    // sent back to the sender
+    pub refund_amount: Uint128,
+    // bought from the otc
+    pub sell_amount: Uint128,
+    // referral fee taken out of ask_amount before it reaches the seller
+    pub seller_fee: Uint128,
+    // referral fee taken out of sell_amount before it reaches the sender
+    pub buyer_fee: Uint128,
+    pub messages: Vec<CosmosMsg>,
+    // sent when the deal of an escrow otc is released instead of by the swap
+    pub held_messages: Vec<CosmosMsg>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralEarningsResponse {
+    pub earnings: Vec<Asset>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BatchItemResult {
+    pub otc_id: u32,
+    pub sell_amount: Uint128,
+    pub ask_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BatchResponse {
+    pub results: Vec<BatchItemResult>,
+}
@@ -0,0 +1,145 @@
+use cosmwasm_std::{Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdError, StdResult, Storage};
+use cw721_base::state::TokenInfo;
+use cw721_base::{Cw721Contract, InstantiateMsg as Cw721InstantiateMsg};
+
+use crate::compliance::check_allowed_addresses;
+use crate::error::ContractError;
+use crate::gating::check_member;
+use crate::state::{DEALS, POSITIONS};
+
+pub type Cw721Msg = cw721_base::ExecuteMsg<Empty, Empty>;
+pub type Cw721QueryMsg = cw721_base::QueryMsg<Empty>;
+
+type Tokens<'a> = Cw721Contract<'a, Empty, Empty, Empty, Empty>;
+
+
+
+/// Right to the undelivered part of a fill, held as a cw721 token of this contract
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Claim {
+    Position(u32),
+    Deal(u32),
+}
+
+
+impl Claim {
+    pub fn token_id(&self) -> String {
+        match self {
+            Claim::Position(id) => format!("position-{}", id),
+            Claim::Deal(id) => format!("deal-{}", id),
+        }
+    }
+
+    fn parse(token_id: &str) -> Option<Self> {
+        let (kind, id) = token_id.split_once('-')?;
+        let id = id.parse().ok()?;
+        match kind {
+            "position" => Some(Claim::Position(id)),
+            "deal" => Some(Claim::Deal(id)),
+            _ => None
+        }
+    }
+}
+
+
+
+/// Sets up the collection with this contract as the only minter
+pub fn instantiate_tokens(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<()> {
+    let msg = Cw721InstantiateMsg {
+        name: "OTC positions".to_string(),
+        symbol: "OTCP".to_string(),
+        minter: env.contract.address.to_string(),
+    };
+    Tokens::default().instantiate(deps, env, info, msg)?;
+    Ok(())
+}
+
+
+
+pub fn mint(storage: &mut dyn Storage, claim: Claim, owner: &Addr) -> StdResult<()> {
+    let tokens = Tokens::default();
+    let token_id = claim.token_id();
+
+    if tokens.tokens.has(storage, &token_id) {
+        return Err(StdError::generic_err(format!("Token {} already exists", token_id)));
+    }
+
+    tokens.tokens.save(storage, &token_id, &TokenInfo {
+        owner: owner.clone(),
+        approvals: vec!(),
+        token_uri: None,
+        extension: Empty {},
+    })?;
+    tokens.increment_tokens(storage)?;
+    Ok(())
+}
+
+
+
+/// Removes the token once the claim is fully delivered
+pub fn burn(storage: &mut dyn Storage, claim: Claim) -> StdResult<()> {
+    let tokens = Tokens::default();
+    let token_id = claim.token_id();
+
+    // claims opened before tokens existed have none
+    if tokens.tokens.has(storage, &token_id) {
+        tokens.tokens.remove(storage, &token_id)?;
+        tokens.decrement_tokens(storage)?;
+    }
+    Ok(())
+}
+
+
+
+/// Transfers and approvals of the claim tokens, the claim follows the new owner
+pub fn try_nft(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Cw721Msg,
+) -> Result<Response, ContractError> {
+
+    let transferred = match &msg {
+        Cw721Msg::TransferNft { token_id, recipient: to } | Cw721Msg::SendNft { token_id, contract: to, .. } => {
+            check_allowed_addresses(deps.storage, [&info.sender, &deps.api.addr_validate(to)?])?;
+            Some(token_id.clone())
+        },
+        Cw721Msg::Approve { .. } | Cw721Msg::Revoke { .. } | Cw721Msg::ApproveAll { .. } | Cw721Msg::RevokeAll { .. } => None,
+        // minted and burned by the contract itself only
+        _ => return Err(ContractError::Unauthorized {})
+    };
+
+    let tokens = Tokens::default();
+    let res = tokens.execute(deps.branch(), env, info, msg)?;
+
+    if let Some(claim) = transferred.as_deref().and_then(Claim::parse) {
+        let owner = tokens.tokens.load(deps.storage, &claim.token_id())?.owner;
+        // claims of gated otcs stay with members
+        let min_member_weight = match claim {
+            Claim::Position(id) => {
+                let mut position = POSITIONS.load(deps.storage, id)?;
+                position.owner = owner.clone();
+                POSITIONS.save(deps.storage, id, &position)?;
+                position.min_member_weight
+            },
+            Claim::Deal(id) => {
+                let mut deal = DEALS.load(deps.storage, id)?;
+                deal.buyer = owner.clone();
+                DEALS.save(deps.storage, id, &deal)?;
+                deal.min_member_weight
+            }
+        };
+
+        if let Some(min_weight) = min_member_weight {
+            check_member(deps.storage, &deps.querier, &owner, min_weight)?;
+        }
+    }
+
+    Ok(res.add_attribute("method", "nft"))
+}
+
+
+
+pub fn query_nft(deps: Deps, env: Env, msg: Cw721QueryMsg) -> StdResult<Binary> {
+    Tokens::default().query(deps, env, msg)
+}
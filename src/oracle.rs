@@ -0,0 +1,169 @@
+use cosmwasm_std::{Api, Decimal, Env, QuerierWrapper, Uint128};
+use cw20::Denom;
+
+use crate::error::ContractError;
+use crate::msg::{NewPeggedPrice, OraclePriceResponse, OracleQueryMsg};
+use crate::state::{Condition, OTCInfo, PeggedPrice, PriceAdjustment, PriceCondition};
+use crate::utils::validate_denom;
+
+
+
+/// Price of the sold asset in the ask asset reported by the oracle, rejected once stale
+fn oracle_price(
+    querier: &QuerierWrapper,
+    env: &Env,
+    oracle: &str,
+    max_staleness: u64,
+    base: Denom,
+    quote: Denom,
+) -> Result<Decimal, ContractError> {
+
+    let res: OraclePriceResponse = querier.query_wasm_smart(oracle, &OracleQueryMsg::Price { base, quote })?;
+
+    if env.block.time.seconds().saturating_sub(res.updated_at.seconds()) > max_staleness {
+        return Err(ContractError::StalePrice {});
+    }
+
+    Ok(res.price)
+}
+
+
+
+fn adjusted(price: Decimal, adjustment: &Option<PriceAdjustment>) -> Decimal {
+    match adjustment {
+        Some(PriceAdjustment::Premium(premium)) => price * (Decimal::one() + *premium),
+        Some(PriceAdjustment::Discount(discount)) => price * (Decimal::one() - *discount),
+        None => price
+    }
+}
+
+
+
+/// Validates the peg and records the current feed price as the reference for the deviation bound
+pub fn new_pegged_price(
+    api: &dyn Api,
+    querier: &QuerierWrapper,
+    env: &Env,
+    otc: &OTCInfo,
+    msg: NewPeggedPrice,
+) -> Result<PeggedPrice, ContractError> {
+
+    let invalid = |reason: &str| Err(ContractError::InvalidPeggedPrice { reason: reason.to_string() });
+
+    if let Some(PriceAdjustment::Discount(discount)) = msg.adjustment {
+        if discount >= Decimal::one() { return invalid("discount must be below 100%"); }
+    }
+
+    let oracle = api.addr_validate(&msg.oracle)?;
+
+    let reference_price = oracle_price(
+        querier,
+        env,
+        oracle.as_str(),
+        msg.max_staleness,
+        otc.sell_asset(),
+        otc.ask_asset()
+    )?;
+
+    if reference_price.is_zero() { return invalid("oracle price must be positive"); }
+
+    Ok(PeggedPrice {
+        oracle,
+        adjustment: msg.adjustment,
+        max_staleness: msg.max_staleness,
+        max_deviation: msg.max_deviation,
+        reference_price,
+    })
+}
+
+
+
+/// Amount of the ask asset needed to take the otc now, the pegged ask comes from the oracle
+pub fn ask_amount(querier: &QuerierWrapper, env: &Env, otc: &OTCInfo) -> Result<Uint128, ContractError> {
+
+    let peg = match &otc.pegged_price {
+        Some(peg) => peg,
+        None => return Ok(otc.current_ask_amount(&env.block))
+    };
+
+    let price = oracle_price(
+        querier,
+        env,
+        peg.oracle.as_str(),
+        peg.max_staleness,
+        otc.sell_asset(),
+        otc.ask_asset()
+    )?;
+
+    if let Some(max_deviation) = peg.max_deviation {
+        let deviation = if price > peg.reference_price {
+            (price - peg.reference_price) / peg.reference_price
+        } else {
+            (peg.reference_price - price) / peg.reference_price
+        };
+        if deviation > max_deviation { return Err(ContractError::PriceDeviation {}); }
+    }
+
+    let ask_amount = otc.sell_amount * adjusted(price, &peg.adjustment);
+
+    if ask_amount.is_zero() {
+        return Err(ContractError::InvalidPeggedPrice { reason: "ask rounds down to zero".to_string() });
+    }
+
+    Ok(ask_amount)
+}
+
+
+
+pub fn validate_condition(api: &dyn Api, condition: Condition) -> Result<Condition, ContractError> {
+
+    let validate = |condition: PriceCondition| -> Result<PriceCondition, ContractError> {
+        if condition.price.is_zero() {
+            return Err(ContractError::InvalidCondition { reason: "price must be positive".to_string() });
+        }
+        Ok(PriceCondition {
+            oracle: api.addr_validate(condition.oracle.as_str())?,
+            base: validate_denom(api, condition.base)?,
+            quote: validate_denom(api, condition.quote)?,
+            ..condition
+        })
+    };
+
+    Ok(match condition {
+        Condition::PriceAtOrBelow(condition) => Condition::PriceAtOrBelow(validate(condition)?),
+        Condition::PriceAtOrAbove(condition) => Condition::PriceAtOrAbove(validate(condition)?),
+        Condition::After(scheduled) => Condition::After(scheduled)
+    })
+}
+
+
+
+/// Fails with the reason the condition of the otc doesn't hold at the current block
+pub fn check_condition(querier: &QuerierWrapper, env: &Env, otc: &OTCInfo) -> Result<(), ContractError> {
+
+    let not_met = |reason: String| Err(ContractError::ConditionNotMet { reason });
+
+    let feed = |condition: &PriceCondition| oracle_price(
+        querier,
+        env,
+        condition.oracle.as_str(),
+        condition.max_staleness,
+        condition.base.clone(),
+        condition.quote.clone()
+    );
+
+    match &otc.condition {
+        None => Ok(()),
+        Some(Condition::After(scheduled)) => {
+            if scheduled.is_triggered(&env.block) { Ok(()) } else { not_met(format!("not before {}", scheduled)) }
+        },
+        Some(Condition::PriceAtOrBelow(condition)) => {
+            let price = feed(condition)?;
+            if price <= condition.price { Ok(()) } else { not_met(format!("price {} is above {}", price, condition.price)) }
+        },
+        Some(Condition::PriceAtOrAbove(condition)) => {
+            let price = feed(condition)?;
+            if price >= condition.price { Ok(()) } else { not_met(format!("price {} is below {}", price, condition.price)) }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use cosmwasm_std::{Addr, Decimal, Deps, Order, StdResult, Storage, Uint128};
+use cw20::Denom;
+
+use crate::error::ContractError;
+use crate::msg::ReferralEarningsResponse;
+use crate::state::{Asset, REFERRAL_EARNINGS};
+use crate::utils::denom_key;
+
+// hard cap for the referral fee the admin can set, 5%
+pub const MAX_REFERRAL_FEE: Decimal = Decimal::raw(50_000_000_000_000_000);
+
+
+
+pub fn validate_referral_fee(fee: Decimal) -> Result<Decimal, ContractError> {
+    if fee > MAX_REFERRAL_FEE {
+        return Err(ContractError::InvalidReferralFee { max: MAX_REFERRAL_FEE });
+    }
+    Ok(fee)
+}
+
+
+
+/// Part of `amount` owed to the referrer, nothing without one
+pub fn referral_fee(rate: Decimal, referrer: Option<&Addr>, amount: Uint128) -> Uint128 {
+    match referrer {
+        Some(_) => amount * rate,
+        None => Uint128::zero()
+    }
+}
+
+
+
+/// Adds a paid out fee to the earnings of the referrer
+pub fn record_referral(storage: &mut dyn Storage, referrer: &Addr, denom: &Denom, fee: Uint128) -> StdResult<()> {
+    if fee.is_zero() { return Ok(()); }
+
+    REFERRAL_EARNINGS.update(storage, (referrer, &denom_key(denom)), |earned| -> StdResult<_> {
+        let mut earned = earned.unwrap_or(Asset { denom: denom.clone(), amount: Uint128::zero() });
+        earned.amount += fee;
+        Ok(earned)
+    })?;
+    Ok(())
+}
+
+
+
+pub fn query_referral_earnings(
+    deps: Deps,
+    address: String,
+) -> StdResult<ReferralEarningsResponse> {
+
+    let address = deps.api.addr_validate(&address)?;
+
+    let earnings = REFERRAL_EARNINGS
+        .prefix(&address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|earned| earned.map(|(_, earned)| earned))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(ReferralEarningsResponse { earnings })
+}
@@ -0,0 +1,306 @@
+use cosmwasm_std::{to_binary, Addr, Deps, DepsMut, Env, Order, Response, StdError, StdResult, Uint128};
+use cw20::{Balance, Denom};
+use cw_storage_plus::Bound;
+use cw_utils::Expiration;
+
+use crate::compliance::{check_allowed_addresses, check_allowed_assets};
+use crate::contract::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::msg::{GetQuotesResponse, GetRfqsResponse};
+use crate::state::{Asset, Quote, RfqInfo, QUOTES, RFQS, STATE};
+use crate::utils::{send_asset_msg, single_asset, validate_denom};
+
+// upper bound for the quotes refunded when an rfq is closed
+const MAX_QUOTES: usize = 30;
+
+
+
+pub fn try_create_rfq(
+    deps: DepsMut,
+    env: Env,
+    requester: &Addr,
+    want: Asset,
+    pay_asset: Denom,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+
+    let mut config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "The factory has been stopped.  No new rfq can be created".to_string()
+            }
+        ));
+    }
+
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    if want.amount.is_zero() { return Err(ContractError::NoFunds {}); }
+
+    let rfq = RfqInfo {
+        requester: requester.clone(),
+        want: Asset {
+            denom: validate_denom(deps.api, want.denom)?,
+            amount: want.amount
+        },
+        pay_asset: validate_denom(deps.api, pay_asset)?,
+        expires,
+    };
+
+    check_allowed_addresses(deps.storage, [requester])?;
+    check_allowed_assets(deps.storage, [&rfq.want.denom, &rfq.pay_asset])?;
+
+    let rfq_id = config.rfq_index;
+
+    RFQS.save(deps.storage, rfq_id, &rfq)?;
+    config.rfq_index += 1;
+    STATE.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .set_data(to_binary(&rfq_id)?)
+        .add_attribute("method", "create_rfq")
+        .add_attribute("rfq_id", rfq_id.to_string())
+    )
+}
+
+
+
+pub fn try_quote(
+    deps: DepsMut,
+    env: Env,
+    maker: &Addr,
+    rfq_id: u32,
+    pay_amount: Uint128,
+    balance: Balance,
+) -> Result<Response, ContractError> {
+
+    let rfq = RFQS.load(deps.storage, rfq_id)?;
+
+    if rfq.expires.is_expired(&env.block) { return Err(ContractError::RfqExpired {}); }
+
+    if rfq.requester == *maker {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "Can't quote your own rfq".to_string()
+            }
+        ));
+    }
+
+    let (asset, amount) = single_asset(balance)?;
+
+    if asset != rfq.want.denom { return Err(ContractError::WrongDenom {}); }
+
+    check_allowed_addresses(deps.storage, [maker, &rfq.requester])?;
+    check_allowed_assets(deps.storage, [&rfq.want.denom, &rfq.pay_asset])?;
+
+    if amount != rfq.want.amount {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "Quote must escrow exactly the wanted amount".to_string()
+            }
+        ));
+    }
+
+    if pay_amount.is_zero() { return Err(ContractError::NoFunds {}); }
+
+    if QUOTES.has(deps.storage, (rfq_id, maker)) {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "Rfq has already been quoted".to_string()
+            }
+        ));
+    }
+
+    let quoted = QUOTES
+        .prefix(rfq_id)
+        .keys_raw(deps.storage, None, None, Order::Ascending)
+        .take(MAX_QUOTES)
+        .count();
+
+    if quoted >= MAX_QUOTES { return Err(ContractError::TooManyQuotes {}); }
+
+    QUOTES.save(deps.storage, (rfq_id, maker), &Quote { pay_amount })?;
+
+    Ok(Response::new()
+        .add_attribute("method", "quote")
+        .add_attribute("pay_amount", pay_amount)
+    )
+}
+
+
+
+pub fn try_withdraw_quote(
+    deps: DepsMut,
+    maker: &Addr,
+    rfq_id: u32,
+) -> Result<Response, ContractError> {
+
+    // escrow denom is kept on the rfq
+    let rfq = RFQS.load(deps.storage, rfq_id)?;
+
+    QUOTES.load(deps.storage, (rfq_id, maker))?;
+    QUOTES.remove(deps.storage, (rfq_id, maker));
+
+    Ok(Response::new()
+        .add_message(send_asset_msg(&rfq.want.denom, rfq.want.amount, maker.as_str())?)
+        .add_attribute("method", "withdraw_quote")
+    )
+}
+
+
+
+/// Removes the rfq along with its quotes, refunding every maker except `except`
+fn close_rfq(
+    deps: DepsMut,
+    rfq_id: u32,
+    rfq: &RfqInfo,
+    except: Option<&Addr>,
+    mut res: Response,
+) -> Result<Response, ContractError> {
+
+    let makers = QUOTES
+        .prefix(rfq_id)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    for maker in makers {
+        QUOTES.remove(deps.storage, (rfq_id, &maker));
+        if Some(&maker) != except {
+            res = res.add_message(send_asset_msg(&rfq.want.denom, rfq.want.amount, maker.as_str())?);
+        }
+    }
+
+    RFQS.remove(deps.storage, rfq_id);
+
+    Ok(res)
+}
+
+
+
+pub fn try_cancel_rfq(
+    deps: DepsMut,
+    sender: &Addr,
+    rfq_id: u32,
+) -> Result<Response, ContractError> {
+
+    let rfq = RFQS.load(deps.storage, rfq_id)?;
+
+    if rfq.requester != *sender { return Err(ContractError::Unauthorized {}); }
+
+    close_rfq(
+        deps,
+        rfq_id,
+        &rfq,
+        None,
+        Response::new().add_attribute("method", "cancel_rfq")
+    )
+}
+
+
+
+pub fn try_accept_quote(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    rfq_id: u32,
+    maker: String,
+    balance: Balance,
+) -> Result<Response, ContractError> {
+
+    let rfq = RFQS.load(deps.storage, rfq_id)?;
+
+    if rfq.requester != *sender { return Err(ContractError::Unauthorized {}); }
+
+    if rfq.expires.is_expired(&env.block) { return Err(ContractError::RfqExpired {}); }
+
+    let maker = deps.api.addr_validate(&maker)?;
+    let quote = QUOTES.load(deps.storage, (rfq_id, &maker))?;
+
+    let (asset, amount) = single_asset(balance)?;
+
+    if asset != rfq.pay_asset { return Err(ContractError::WrongDenom {}); }
+
+    check_allowed_addresses(deps.storage, [sender, &maker])?;
+    check_allowed_assets(deps.storage, [&rfq.want.denom, &rfq.pay_asset])?;
+
+    if amount < quote.pay_amount {
+        return Err(ContractError::Std(
+            StdError::GenericErr {
+                msg: "Sent amount is smaller than what being asked".to_string()
+            }
+        ));
+    }
+
+    let mut res = Response::new()
+        .add_message(send_asset_msg(&rfq.pay_asset, quote.pay_amount, maker.as_str())?)
+        .add_message(send_asset_msg(&rfq.want.denom, rfq.want.amount, sender.as_str())?)
+        .add_attribute("method", "accept_quote")
+        .add_attribute("maker", maker.as_str());
+
+    if amount > quote.pay_amount {
+        res = res.add_message(send_asset_msg(&rfq.pay_asset, amount - quote.pay_amount, sender.as_str())?);
+    }
+
+    close_rfq(deps, rfq_id, &rfq, Some(&maker), res)
+}
+
+
+
+pub fn query_rfqs(
+    deps: Deps,
+    env: Env,
+    include_expired: bool,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<GetRfqsResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let rfqs = RFQS
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending
+        )
+        .filter(|rfq| match rfq {
+            Ok((_, rfq)) => include_expired || !rfq.expires.is_expired(&env.block),
+            Err(_) => true
+        })
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetRfqsResponse { rfqs })
+}
+
+
+
+pub fn query_quotes(
+    deps: Deps,
+    rfq_id: u32,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<GetQuotesResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+
+    let quotes = QUOTES
+        .prefix(rfq_id)
+        .range(
+            deps.storage,
+            start_after.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending
+        )
+        .take(limit)
+        .map(|quote| quote.map(|(maker, quote)| (maker.into_string(), quote)))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetQuotesResponse { quotes })
+}
@@ -1,9 +1,16 @@
-use cw_utils::Expiration;
+use cw20::Denom;
+use cw_utils::{Duration, Expiration, Scheduled};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{CanonicalAddr, Addr, Uint128};
+use cosmwasm_std::{Api, CanonicalAddr, Addr, Coin, Uint128, BlockInfo, Binary, CosmosMsg, Decimal, Decimal256, Empty, StdResult, Storage};
 use cw_storage_plus::{Item, Map};
+use cw_controllers::Hooks;
+
+use crate::utils::{denom_key, payout_msg, send_asset_msg};
+
+// basis points the shares of a payout split add up to
+pub const TOTAL_BPS: u16 = 10_000;
 
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -11,6 +18,42 @@ pub struct State {
     pub admin: CanonicalAddr,
     pub index: u32,
     pub active: bool,
+    #[serde(default)]
+    pub auction_index: u32,
+    #[serde(default)]
+    pub rfq_index: u32,
+    #[serde(default)]
+    pub price_improvement: PriceImprovement,
+    // share of the received amount paid to the referrer of each side of a fill
+    #[serde(default)]
+    pub referral_fee: Decimal,
+    #[serde(default)]
+    pub deal_index: u32,
+    #[serde(default)]
+    pub position_index: u32,
+    #[serde(default)]
+    pub program_index: u32,
+    // cw4 group gated otcs check their counterparties against
+    #[serde(default)]
+    pub member_group: Option<Addr>,
+    // only assets on the allow-list can be traded when set
+    #[serde(default)]
+    pub restrict_assets: bool,
+    // refundable native deposit taken for every new otc
+    #[serde(default)]
+    pub listing_deposit: Option<Coin>,
+    #[serde(default)]
+    pub max_open_otcs: Option<u32>,
+}
+
+
+/// Receiver of the difference between the prices of two matched otcs
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceImprovement {
+    #[default]
+    Taker,
+    Collector(Addr),
 }
 
 
@@ -21,6 +64,90 @@ pub struct UserInfo {
 }
 
 
+/// Ask that decays from the otc `ask_amount` down to `end_ask_amount`
+/// between `start` and `end`. Both points must be of the same kind
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DutchAuction {
+    pub end_ask_amount: Uint128,
+    pub start: Scheduled,
+    pub end: Scheduled,
+    // number of equal price drops, decays linearly if not set
+    pub steps: Option<u64>,
+}
+
+
+/// Share of the proceeds of an otc in basis points
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PayoutShare {
+    pub address: Addr,
+    pub bps: u16,
+}
+
+
+/// Ask priced off an oracle feed at fill time
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PeggedPrice {
+    // contract answering `OracleQueryMsg::Price`
+    pub oracle: Addr,
+    pub adjustment: Option<PriceAdjustment>,
+    // oldest accepted feed update in seconds
+    pub max_staleness: u64,
+    // largest accepted relative move of the feed away from reference_price
+    pub max_deviation: Option<Decimal>,
+    // feed price when the otc was created
+    pub reference_price: Decimal,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceAdjustment {
+    Premium(Decimal),
+    Discount(Decimal),
+}
+
+
+/// Oracle price of `base` in `quote` compared against `price`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceCondition {
+    pub oracle: Addr,
+    pub base: Denom,
+    pub quote: Denom,
+    pub price: Decimal,
+    // oldest accepted feed update in seconds
+    pub max_staleness: u64,
+}
+
+
+/// Must hold for the otc to be filled
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    PriceAtOrBelow(PriceCondition),
+    PriceAtOrAbove(PriceCondition),
+    After(Scheduled),
+}
+
+
+/// Holds the swap of an otc until the buyer confirms the off-chain leg
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Escrow {
+    // resolves disputes, none can be opened without one
+    pub arbiter: Option<Addr>,
+    // time the buyer has to confirm or dispute after the swap
+    pub confirm_period: Duration,
+}
+
+
+/// Release of the sold asset to the buyer measured from the fill,
+/// nothing before the cliff and linear up to the end of the duration
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingSchedule {
+    pub cliff: Duration,
+    pub duration: Duration,
+}
+
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct OTCInfo {
     pub seller: CanonicalAddr,
@@ -35,8 +162,429 @@ pub struct OTCInfo {
     pub expires: Expiration,
     pub user_info: Option<UserInfo>,
     pub description: Option<String>,
+    pub dutch_auction: Option<DutchAuction>,
+    // receiver of the proceeds instead of the seller
+    pub recipient: Option<Addr>,
+    // proceeds are sent with this message through cw20 send or wasm execute
+    pub payout_msg: Option<Binary>,
+    // frontend or broker paid a referral fee out of the proceeds
+    pub referrer: Option<Addr>,
+    // proceeds are split across these addresses instead of the single recipient
+    pub payout_split: Option<Vec<PayoutShare>>,
+    // ask follows an oracle, ask_amount holds the ask at creation then
+    pub pegged_price: Option<PeggedPrice>,
+    pub condition: Option<Condition>,
+    pub escrow: Option<Escrow>,
+    // sold asset vests to the buyer instead of being sent on the fill
+    pub vesting: Option<VestingSchedule>,
+    // fills left of a recurring otc, each of sell_amount, a single one when not set
+    #[serde(default)]
+    pub fills_left: Option<u32>,
+    // buyers must be members of the configured group with at least this weight
+    #[serde(default)]
+    pub min_member_weight: Option<u64>,
+    // listing deposit returned to the seller on the last fill or cancel
+    #[serde(default)]
+    pub deposit: Option<Coin>,
+}
+
+
+impl OTCInfo {
+    pub fn sell_asset(&self) -> Denom {
+        match &self.sell_address {
+            Some(address) => Denom::Cw20(address.clone()),
+            None => Denom::Native(self.sell_denom.clone().unwrap_or_default())
+        }
+    }
+
+    pub fn ask_asset(&self) -> Denom {
+        match &self.ask_address {
+            Some(address) => Denom::Cw20(address.clone()),
+            None => Denom::Native(self.ask_denom.clone().unwrap_or_default())
+        }
+    }
+
+    /// Amount of the ask asset needed to take the otc at the given block,
+    /// the ask at creation for pegged otcs
+    pub fn current_ask_amount(&self, block: &BlockInfo) -> Uint128 {
+        let auction = match &self.dutch_auction {
+            Some(auction) => auction,
+            None => return self.ask_amount
+        };
+
+        let (start, end, now) = match (auction.start, auction.end) {
+            (Scheduled::AtHeight(start), Scheduled::AtHeight(end)) => (start, end, block.height),
+            (Scheduled::AtTime(start), Scheduled::AtTime(end)) => (start.nanos(), end.nanos(), block.time.nanos()),
+            // rejected on creation
+            _ => return self.ask_amount
+        };
+
+        if now <= start { return self.ask_amount; }
+        if now >= end { return auction.end_ask_amount; }
+
+        let drop = self.ask_amount - auction.end_ask_amount;
+
+        let dropped = match auction.steps {
+            Some(steps) => {
+                let passed = (now - start) as u128 * steps as u128 / (end - start) as u128;
+                drop.multiply_ratio(passed, steps)
+            },
+            None => drop.multiply_ratio(now - start, end - start)
+        };
+
+        self.ask_amount - dropped
+    }
+
+    /// Address the proceeds of a fill go to
+    pub fn payout_address(&self, api: &dyn Api) -> StdResult<Addr> {
+        match &self.recipient {
+            Some(recipient) => Ok(recipient.clone()),
+            None => api.addr_humanize(&self.seller)
+        }
+    }
+
+    /// Messages paying `amount` of the proceeds to the payout split or the payout address.
+    /// Rounding dust of a split goes to its first share
+    pub fn payout_msgs(&self, api: &dyn Api, asset: &Denom, amount: Uint128) -> StdResult<Vec<CosmosMsg>> {
+        let split = match &self.payout_split {
+            Some(split) => split,
+            None => return Ok(vec!(payout_msg(asset, amount, self.payout_address(api)?.as_str(), self.payout_msg.clone())?))
+        };
+
+        let mut amounts: Vec<Uint128> = split
+            .iter()
+            .map(|share| amount.multiply_ratio(share.bps, TOTAL_BPS))
+            .collect();
+
+        let dust = amount - amounts.iter().sum::<Uint128>();
+        amounts[0] += dust;
+
+        split
+            .iter()
+            .zip(amounts)
+            .filter(|(_, amount)| !amount.is_zero())
+            .map(|(share, amount)| send_asset_msg(asset, amount, share.address.as_str()))
+            .collect()
+    }
+
+    /// Addresses other than the seller paid out of the fills, the recipient, the referrer and the payout split
+    pub fn payees(&self) -> impl Iterator<Item = &Addr> {
+        self.recipient
+            .iter()
+            .chain(self.referrer.iter())
+            .chain(self.payout_split.iter().flatten().map(|share| &share.address))
+    }
+
+    /// Only plain fixed price otcs settle against each other
+    pub fn matchable(&self) -> bool {
+        self.dutch_auction.is_none() && 
+        self.pegged_price.is_none() && 
+        self.escrow.is_none() && 
+        self.vesting.is_none() &&
+        self.fills_left.is_none() &&
+        self.min_member_weight.is_none()
+    }
+
+    /// Sold asset held for all the fills left
+    pub fn escrowed(&self) -> Uint128 {
+        self.sell_amount * Uint128::from(self.fills_left.unwrap_or(1))
+    }
+
+    /// Key grouping otcs selling and asking the same assets
+    pub fn pair_key(&self) -> String {
+        pair_key(&self.sell_asset(), &self.ask_asset())
+    }
+
+    /// Ask amount per unit of the sold asset at the given block
+    pub fn current_price(&self, block: &BlockInfo) -> Decimal256 {
+        Decimal256::from_ratio(self.current_ask_amount(block), self.sell_amount)
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Bid {
+    pub bidder: Addr,
+    pub amount: Uint128,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuctionInfo {
+    pub seller: Addr,
+    pub sell_asset: Denom,
+    pub sell_amount: Uint128,
+    pub bid_asset: Denom,
+    // auction is settled without a sale if the highest bid is below
+    pub reserve_price: Uint128,
+    // every bid after the first must top the previous one by at least this
+    pub min_increment: Uint128,
+    pub end: Expiration,
+    // bids placed closer than this to the end push the end back
+    pub extension: Option<Duration>,
+    pub highest_bid: Option<Bid>,
+    pub user_info: Option<UserInfo>,
+    pub description: Option<String>,
+    // commit-reveal bidding, bids are open if not set
+    pub sealed: Option<SealedBidding>,
+}
+
+
+/// Bids are committed until the auction `end` and revealed until `reveal_end`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SealedBidding {
+    pub reveal_end: Expiration,
+    // unrevealed deposits go to the seller instead of being refunded
+    pub slash_unrevealed: bool,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SealedBid {
+    // sha256 of the bid amount as 16 big-endian bytes followed by the salt
+    pub commitment: Binary,
+    pub deposit: Uint128,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Asset {
+    pub denom: Denom,
+    pub amount: Uint128,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RfqInfo {
+    pub requester: Addr,
+    pub want: Asset,
+    pub pay_asset: Denom,
+    pub expires: Expiration,
+}
+
+
+/// Maker quote for an rfq, escrowing the wanted asset
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Quote {
+    // amount of the pay asset asked for the wanted asset
+    pub pay_amount: Uint128,
+}
+
+
+
+/// Swap of an escrow otc waiting for the buyer to confirm the off-chain leg
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Deal {
+    pub otc_id: u32,
+    pub seller: Addr,
+    // current owner of the deal token
+    pub buyer: Addr,
+    pub sell_asset: Asset,
+    pub payment: Asset,
+    pub arbiter: Option<Addr>,
+    // released to the seller once passed without a dispute,
+    // end of the resolution period once disputed
+    pub deadline: Expiration,
+    pub disputed: bool,
+    // settlement of the swap to the seller and the referrers sent on release
+    pub release: Vec<CosmosMsg>,
+    // part of the sold asset sent to the buyer on release
+    #[serde(default)]
+    pub delivery: Uint128,
+    // referral fees within the settlement, recorded on release
+    pub referrals: Vec<(Addr, Denom, Uint128)>,
+    // weight the deal token owner must keep in the member group, from the gated otc
+    #[serde(default)]
+    pub min_member_weight: Option<u64>,
+}
+
+
+
+/// Assets of a pair with the number of its open otcs
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Pair {
+    pub sell_asset: Denom,
+    pub ask_asset: Denom,
+    pub open: u32,
+}
+
+
+
+/// Sold asset of a vesting otc held for the buyer
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Position {
+    pub otc_id: u32,
+    // current owner of the position token
+    pub owner: Addr,
+    pub asset: Asset,
+    // block of the fill the schedule starts from
+    pub start: Scheduled,
+    pub schedule: VestingSchedule,
+    pub claimed: Uint128,
+    // weight the position token owner must keep in the member group, from the gated otc
+    #[serde(default)]
+    pub min_member_weight: Option<u64>,
+}
+
+
+impl Position {
+    /// Part of the asset released at the given block, claimed or not
+    pub fn vested(&self, block: &BlockInfo) -> Uint128 {
+        let (elapsed, cliff, duration) = match (self.start, self.schedule.cliff, self.schedule.duration) {
+            (Scheduled::AtHeight(start), Duration::Height(cliff), Duration::Height(duration)) => {
+                (block.height.saturating_sub(start), cliff, duration)
+            },
+            (Scheduled::AtTime(start), Duration::Time(cliff), Duration::Time(duration)) => {
+                (block.time.seconds().saturating_sub(start.seconds()), cliff, duration)
+            },
+            // rejected on creation
+            _ => return Uint128::zero()
+        };
+
+        if elapsed < cliff { return Uint128::zero(); }
+        if elapsed >= duration { return self.asset.amount; }
+
+        self.asset.amount.multiply_ratio(elapsed, duration)
+    }
+
+    pub fn claimable(&self, block: &BlockInfo) -> Uint128 {
+        self.vested(block) - self.claimed
+    }
+}
+
+
+/// Scheduled buys of `buy_asset` paid from an escrow of the pay asset
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Program {
+    pub owner: Addr,
+    // pay asset left in escrow
+    pub escrow: Asset,
+    pub buy_asset: Denom,
+    // most of the pay asset spent on a single installment
+    pub amount: Uint128,
+    // highest accepted price in the pay asset per unit bought
+    pub max_price: Decimal256,
+    pub interval: Duration,
+    // the next installment can be run once this has passed
+    pub next: Expiration,
+    pub installments_left: u32,
+    // paid to the cranker out of the escrow for every installment run
+    pub tip: Uint128,
 }
 
 
 pub const STATE: Item<State> = Item::new("state");
-pub const OTCS: Map<u32, OTCInfo> = Map::new("otcs");
\ No newline at end of file
+pub const OTCS: Map<u32, OTCInfo> = Map::new("otcs");
+// fixed price otcs by pair and big-endian ask per unit sold
+pub const PRICE_INDEX: Map<(&str, &[u8], u32), Empty> = Map::new("price_index");
+// open otcs by pair key
+pub const PAIRS: Map<&str, Pair> = Map::new("pairs");
+pub const AUCTIONS: Map<u32, AuctionInfo> = Map::new("auctions");
+// unrevealed bids of sealed auctions
+pub const SEALED_BIDS: Map<(u32, &Addr), SealedBid> = Map::new("sealed_bids");
+pub const RFQS: Map<u32, RfqInfo> = Map::new("rfqs");
+pub const QUOTES: Map<(u32, &Addr), Quote> = Map::new("quotes");
+// escrowed swaps waiting for release
+pub const DEALS: Map<u32, Deal> = Map::new("deals");
+// vesting deliveries claimed by the buyers
+pub const POSITIONS: Map<u32, Position> = Map::new("positions");
+// total referral fees paid out by referrer and asset key
+pub const REFERRAL_EARNINGS: Map<(&Addr, &str), Asset> = Map::new("referral_earnings");
+// scheduled buy programs run by the crank
+pub const PROGRAMS: Map<u32, Program> = Map::new("programs");
+// addresses blocked from trading
+pub const DENIED_ADDRESSES: Map<&Addr, Empty> = Map::new("denied_addresses");
+// assets by key that can be traded while the allow-list is enforced
+pub const ALLOWED_ASSETS: Map<&str, Denom> = Map::new("allowed_assets");
+// otcs by expiry kind, 0 for heights and 1 for times, and the height or time in nanos
+pub const EXPIRY_INDEX: Map<(u8, u64, u32), Empty> = Map::new("expiry_index");
+// open otcs by canonical seller address
+pub const OPEN_OTCS: Map<&[u8], u32> = Map::new("open_otcs");
+// smallest amount of an asset by key an otc can sell or ask for
+pub const MIN_NOTIONAL: Map<&str, Asset> = Map::new("min_notional");
+// prepaid listing deposits for otcs created from cw20 sends
+pub const LISTING_CREDITS: Map<&Addr, Coin> = Map::new("listing_credits");
+// contracts notified on otc lifecycle events
+pub const HOOKS: Hooks = Hooks::new("hooks");
+
+
+pub fn pair_key(sell_asset: &Denom, ask_asset: &Denom) -> String {
+    format!("{}|{}", denom_key(sell_asset), denom_key(ask_asset))
+}
+
+
+pub fn price_key(price: Decimal256) -> [u8; 32] {
+    price.atomics().to_be_bytes()
+}
+
+
+fn index_key(otc: &OTCInfo) -> Option<(String, [u8; 32])> {
+    // decaying and pegged asks can't be kept in order
+    if otc.dutch_auction.is_some() || otc.pegged_price.is_some() { return None; }
+    Some((otc.pair_key(), price_key(Decimal256::from_ratio(otc.ask_amount, otc.sell_amount))))
+}
+
+
+pub fn expiry_key(expires: &Expiration) -> Option<(u8, u64)> {
+    match expires {
+        Expiration::AtHeight(height) => Some((0, *height)),
+        Expiration::AtTime(time) => Some((1, time.nanos())),
+        Expiration::Never {} => None
+    }
+}
+
+
+/// Saves an otc keeping the secondary indexes up to date
+pub fn save_otc(storage: &mut dyn Storage, id: u32, otc: &OTCInfo) -> StdResult<()> {
+    match OTCS.may_load(storage, id)? {
+        Some(old) => {
+            if let Some((pair, price)) = index_key(&old) {
+                PRICE_INDEX.remove(storage, (&pair, &price, id));
+            }
+            if let Some((kind, at)) = expiry_key(&old.expires) {
+                EXPIRY_INDEX.remove(storage, (kind, at, id));
+            }
+        },
+        None => {
+            OPEN_OTCS.update(storage, otc.seller.as_slice(), |open| -> StdResult<_> { Ok(open.unwrap_or_default() + 1) })?;
+            PAIRS.update(storage, &otc.pair_key(), |pair| -> StdResult<_> {
+                Ok(Pair {
+                    sell_asset: otc.sell_asset(),
+                    ask_asset: otc.ask_asset(),
+                    open: pair.map(|pair| pair.open).unwrap_or_default() + 1
+                })
+            })?;
+        }
+    }
+    if let Some((pair, price)) = index_key(otc) {
+        PRICE_INDEX.save(storage, (&pair, &price, id), &Empty {})?;
+    }
+    if let Some((kind, at)) = expiry_key(&otc.expires) {
+        EXPIRY_INDEX.save(storage, (kind, at, id), &Empty {})?;
+    }
+    OTCS.save(storage, id, otc)
+}
+
+
+/// Removes an otc along with its secondary index entries
+pub fn remove_otc(storage: &mut dyn Storage, id: u32) -> StdResult<()> {
+    let otc = OTCS.load(storage, id)?;
+    if let Some((pair, price)) = index_key(&otc) {
+        PRICE_INDEX.remove(storage, (&pair, &price, id));
+    }
+    if let Some((kind, at)) = expiry_key(&otc.expires) {
+        EXPIRY_INDEX.remove(storage, (kind, at, id));
+    }
+    OTCS.remove(storage, id);
+    // otcs listed before the count was kept aren't in it
+    OPEN_OTCS.update(storage, otc.seller.as_slice(), |open| -> StdResult<_> { Ok(open.unwrap_or_default().saturating_sub(1)) })?;
+    if let Some(mut pair) = PAIRS.may_load(storage, &otc.pair_key())? {
+        pair.open = pair.open.saturating_sub(1);
+        if pair.open == 0 {
+            PAIRS.remove(storage, &otc.pair_key());
+        } else {
+            PAIRS.save(storage, &otc.pair_key(), &pair)?;
+        }
+    }
+    Ok(())
+}
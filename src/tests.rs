@@ -1,15 +1,25 @@
 #[cfg(test)]
 mod tests {
-    use crate::contract::{execute, instantiate, query};
+    use crate::contract::{execute, instantiate, query, reply};
     use crate::error::ContractError;
-    use crate::msg::{NewOTC, NewOTCResponse, ExecuteMsg, InstantiateMsg, QueryMsg, GetOTCsResponse};
+    use crate::msg::{ReceiveMsg, NewOTC, NewOTCResponse, ExecuteMsg, InstantiateMsg, QueryMsg, GetOTCsResponse, CurrentPriceResponse, NewAuction, GetRfqsResponse, GetQuotesResponse, MatchPair, PairBookResponse, PairsResponse, BestOfferResponse, SimulateSwapResponse, BatchNewOTC, BatchResponse, OtcHookMsg, OtcHookExecuteMsg, ReferralEarningsResponse, NewPeggedPrice, OracleQueryMsg, OraclePriceResponse, FillableResponse, PositionResponse, GetPositionsResponse, NewProgram, GetProgramsResponse, DenyListResponse, AllowListResponse, ListingCreditResponse, ExpiredCountResponse};
+    use crate::state::{DutchAuction, AuctionInfo, SealedBidding, Asset, PriceImprovement, PayoutShare, PriceAdjustment, Condition, PriceCondition, Escrow, Deal, VestingSchedule, OTCS};
 
     use cosmwasm_std::testing::{
         mock_dependencies, mock_env, mock_info, mock_dependencies_with_balances, 
     };
-    use cosmwasm_std::{coins, from_binary, DepsMut, Response, Uint128,  Coin, Deps, Api, Env};
-    use cw20::Balance;
-    use cw_utils::{NativeBalance, Expiration};
+    use cosmwasm_std::{coins, from_binary, DepsMut, Response, Uint128,  Coin, Deps, Api, Env, CosmosMsg, BankMsg, Binary, Addr, Decimal, Decimal256, WasmMsg, to_binary, Reply, ReplyOn, SubMsgResult, Event, Timestamp, Attribute, Empty, MessageInfo, StdResult};
+    use cw_storage_plus::Map;
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+    use crate::utils::denom_key;
+    use cw_controllers::HooksResponse;
+    use cw721::{NumTokensResponse, OwnerOfResponse, TokensResponse};
+    use cw4::Member;
+    use cw_multi_test::{App, ContractWrapper, Executor};
+    use sha2::{Digest, Sha256};
+    use cw20::{Balance, Denom, Cw20ReceiveMsg, Cw20ExecuteMsg};
+    use cw_utils::{NativeBalance, Expiration, Scheduled, Duration};
 
 
     fn sell_native_ask_native(deps : DepsMut, count: u32, expires: Option<Expiration>) {
@@ -35,6 +45,7 @@ mod tests {
             expires,
             user_info: None,
             description: None,
+            ..Default::default()
         });
         
 
@@ -171,7 +182,7 @@ mod tests {
         //let bigger_amount_info = mock_info("bob", &coins(100, "token_2"));
         let right_info = mock_info("bob", &coins(10, "token_2"));
 
-        let msg = ExecuteMsg::Swap { otc_id: count.clone() };
+        let msg = ExecuteMsg::Swap { otc_id: count.clone(), recipient: None, payout_msg: None, referrer: None };
 
         let res = execute(deps.as_mut(), mock_env(), same_person_info, msg.clone()).unwrap_err();
         assert_eq!(res.to_string(), "Generic error: Can't swap with yourself");
@@ -273,6 +284,1881 @@ mod tests {
 
 
 
+    fn current_price(deps: Deps, env: Env, otc_id: u32) -> Uint128 {
+        let res = query(deps, env, QueryMsg::CurrentPrice { otc_id }).unwrap();
+        let value: CurrentPriceResponse = from_binary(&res).unwrap();
+        value.ask_amount
+    }
+
+
+    #[test]
+    fn dutch_auction_price_decays() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let start = env.block.height;
+
+        instantiate_contract(deps.as_mut());
+
+        let new_otc = |steps: Option<u64>| ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(100, "token_2"))),
+            expires: Some(Expiration::AtHeight(start + 200)),
+            dutch_auction: Some(DutchAuction {
+                end_ask_amount: Uint128::from(50u8),
+                start: Scheduled::AtHeight(start),
+                end: Scheduled::AtHeight(start + 100),
+                steps,
+            }),
+            ..Default::default()
+        });
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "token_1")), new_otc(None)).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "token_1")), new_otc(Some(4))).unwrap();
+
+        assert_eq!(current_price(deps.as_ref(), env.clone(), 0), Uint128::from(100u8));
+
+        env.block.height = start + 30;
+        assert_eq!(current_price(deps.as_ref(), env.clone(), 0), Uint128::from(85u8));
+        assert_eq!(current_price(deps.as_ref(), env.clone(), 1), Uint128::from(88u8));
+
+        env.block.height = start + 150;
+        assert_eq!(current_price(deps.as_ref(), env.clone(), 0), Uint128::from(50u8));
+        assert_eq!(current_price(deps.as_ref(), env.clone(), 1), Uint128::from(50u8));
+
+
+        let rising = ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(100, "token_2"))),
+            dutch_auction: Some(DutchAuction {
+                end_ask_amount: Uint128::from(150u8),
+                start: Scheduled::AtHeight(start),
+                end: Scheduled::AtHeight(start + 100),
+                steps: None,
+            }),
+            ..Default::default()
+        });
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "token_1")), rising).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDutchAuction { .. }));
+
+
+        // overpaying at the current price refunds the difference
+        env.block.height = start + 30;
+        let res = execute(
+            deps.as_mut(), 
+            env.clone(), 
+            mock_info("bob", &coins(100, "token_2")), 
+            ExecuteMsg::Swap { otc_id: 0, recipient: None, payout_msg: None, referrer: None }
+        ).unwrap();
+
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "alice".to_string(), 
+            amount: coins(85, "token_2") 
+        }));
+        assert_eq!(res.messages[1].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "bob".to_string(), 
+            amount: coins(15, "token_2") 
+        }));
+
+        env.block.height = start + 200;
+        let err = execute(
+            deps.as_mut(), 
+            env, 
+            mock_info("bob", &coins(100, "token_2")), 
+            ExecuteMsg::Swap { otc_id: 1, recipient: None, payout_msg: None, referrer: None }
+        ).unwrap_err();
+        assert!(matches!(err, ContractError::OtcExpired {}));
+    }
+
+
+
+
+    fn create_auction(deps: DepsMut, env: Env, reserve_price: u128) {
+        let msg = ExecuteMsg::CreateAuction(NewAuction {
+            bid_asset: Denom::Native("token_2".to_string()),
+            reserve_price: Uint128::from(reserve_price),
+            min_increment: Uint128::from(5u8),
+            end: Expiration::AtHeight(env.block.height + 100),
+            extension: Some(Duration::Height(10)),
+            user_info: None,
+            description: None,
+            sealed: None,
+        });
+        execute(deps, env, mock_info("alice", &coins(5, "token_1")), msg).unwrap();
+    }
+
+
+    fn get_auction(deps: Deps, auction_id: u32) -> AuctionInfo {
+        let res = query(deps, mock_env(), QueryMsg::GetAuction { auction_id }).unwrap();
+        from_binary(&res).unwrap()
+    }
+
+
+    #[test]
+    fn english_auction_bids_and_settles() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let start = env.block.height;
+
+        instantiate_contract(deps.as_mut());
+        create_auction(deps.as_mut(), env.clone(), 15);
+
+        let bid = ExecuteMsg::Bid { auction_id: 0 };
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(10, "token_2")), bid.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "Generic error: Can't bid on your own auction");
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(10, "token_3")), bid.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::WrongDenom {}));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(10, "token_2")), bid.clone()).unwrap();
+        assert_eq!(res.messages.len(), 0);
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("carol", &coins(14, "token_2")), bid.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::BidTooLow { min } if min == Uint128::from(15u8)));
+
+        // outbid bidder is refunded and a late bid pushes the end back
+        env.block.height = start + 95;
+        let res = execute(deps.as_mut(), env.clone(), mock_info("carol", &coins(15, "token_2")), bid.clone()).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "bob".to_string(), 
+            amount: coins(10, "token_2") 
+        }));
+        assert_eq!(get_auction(deps.as_ref(), 0).end, Expiration::AtHeight(start + 105));
+
+        env.block.height = start + 100;
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), ExecuteMsg::Settle { auction_id: 0 }).unwrap_err();
+        assert!(matches!(err, ContractError::AuctionNotEnded {}));
+
+        env.block.height = start + 105;
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(20, "token_2")), bid).unwrap_err();
+        assert!(matches!(err, ContractError::AuctionEnded {}));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), ExecuteMsg::Settle { auction_id: 0 }).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "alice".to_string(), 
+            amount: coins(15, "token_2") 
+        }));
+        assert_eq!(res.messages[1].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "carol".to_string(), 
+            amount: coins(5, "token_1") 
+        }));
+        assert!(query(deps.as_ref(), env, QueryMsg::GetAuction { auction_id: 0 }).is_err());
+    }
+
+
+    #[test]
+    fn english_auction_below_reserve_returns_escrow() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+        create_auction(deps.as_mut(), env.clone(), 100);
+
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(10, "token_2")), ExecuteMsg::Bid { auction_id: 0 }).unwrap();
+
+        env.block.height += 100;
+        let res = execute(deps.as_mut(), env, mock_info("carol", &[]), ExecuteMsg::Settle { auction_id: 0 }).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "alice".to_string(), 
+            amount: coins(5, "token_1") 
+        }));
+        assert_eq!(res.messages[1].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "bob".to_string(), 
+            amount: coins(10, "token_2") 
+        }));
+    }
+
+
+
+
+    fn commitment(amount: u128, salt: &str) -> Binary {
+        let mut hasher = Sha256::new();
+        hasher.update(amount.to_be_bytes());
+        hasher.update(salt.as_bytes());
+        Binary::from(hasher.finalize().as_slice())
+    }
+
+
+    #[test]
+    fn sealed_auction_commit_reveal() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let start = env.block.height;
+
+        instantiate_contract(deps.as_mut());
+
+        let msg = ExecuteMsg::CreateAuction(NewAuction {
+            bid_asset: Denom::Native("token_2".to_string()),
+            reserve_price: Uint128::from(10u8),
+            min_increment: Uint128::zero(),
+            end: Expiration::AtHeight(start + 10),
+            extension: None,
+            user_info: None,
+            description: None,
+            sealed: Some(SealedBidding { 
+                reveal_end: Expiration::AtHeight(start + 20), 
+                slash_unrevealed: true 
+            }),
+        });
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "token_1")), msg).unwrap();
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(30, "token_2")), ExecuteMsg::Bid { auction_id: 0 }).unwrap_err();
+        assert!(matches!(err, ContractError::SealedAuction {}));
+
+        let err = execute(
+            deps.as_mut(), 
+            env.clone(), 
+            mock_info("erin", &coins(9, "token_2")), 
+            ExecuteMsg::CommitBid { auction_id: 0, commitment: commitment(9, "erin") }
+        ).unwrap_err();
+        assert!(matches!(err, ContractError::BidTooLow { .. }));
+
+        for (bidder, deposit, amount) in [("bob", 30, 20), ("carol", 25, 25), ("dave", 40, 40)] {
+            execute(
+                deps.as_mut(), 
+                env.clone(), 
+                mock_info(bidder, &coins(deposit, "token_2")), 
+                ExecuteMsg::CommitBid { auction_id: 0, commitment: commitment(amount, bidder) }
+            ).unwrap();
+        }
+
+        let reveal = |amount: u128, salt: &str| ExecuteMsg::RevealBid { 
+            auction_id: 0, 
+            amount: Uint128::from(amount), 
+            salt: Binary::from(salt.as_bytes()) 
+        };
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), reveal(20, "bob")).unwrap_err();
+        assert!(matches!(err, ContractError::NotRevealPhase {}));
+
+        env.block.height = start + 10;
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), reveal(21, "bob")).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCommitment {}));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), reveal(20, "bob")).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "bob".to_string(), 
+            amount: coins(10, "token_2") 
+        }));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), reveal(25, "carol")).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "bob".to_string(), 
+            amount: coins(20, "token_2") 
+        }));
+
+        env.block.height = start + 20;
+
+        // dave never revealed so the deposit is slashed to the seller
+        let res = execute(deps.as_mut(), env, mock_info("bob", &[]), ExecuteMsg::Settle { auction_id: 0 }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(40, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(25, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "carol".to_string(), amount: coins(5, "token_1") }),
+        ));
+    }
+
+
+
+
+    #[test]
+    fn rfq_accepts_one_quote_and_refunds_others() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::CreateRfq { 
+            want: Asset { denom: Denom::Native("token_1".to_string()), amount: Uint128::from(100u8) }, 
+            pay_asset: Denom::Native("token_2".to_string()), 
+            expires: None 
+        }).unwrap();
+
+        let quote = |pay_amount: u128| ExecuteMsg::Quote { rfq_id: 0, pay_amount: Uint128::from(pay_amount) };
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(90, "token_1")), quote(50)).unwrap_err();
+        assert_eq!(err.to_string(), "Generic error: Quote must escrow exactly the wanted amount");
+
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(100, "token_1")), quote(50)).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("carol", &coins(100, "token_1")), quote(40)).unwrap();
+
+        let rfqs: GetRfqsResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetRfqs { 
+            include_expired: None, start_after: None, limit: None 
+        }).unwrap()).unwrap();
+        assert_eq!(rfqs.rfqs.len(), 1);
+
+        let quotes: GetQuotesResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetQuotes { 
+            rfq_id: 0, start_after: None, limit: None 
+        }).unwrap()).unwrap();
+        assert_eq!(quotes.quotes.len(), 2);
+        assert_eq!(quotes.quotes[1].0, "carol");
+
+        let accept = ExecuteMsg::AcceptQuote { rfq_id: 0, maker: "carol".to_string() };
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(40, "token_2")), accept.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(40, "token_2")), accept).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "carol".to_string(), amount: coins(40, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(100, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(100, "token_1") }),
+        ));
+
+        let quotes: GetQuotesResponse = from_binary(&query(deps.as_ref(), env, QueryMsg::GetQuotes { 
+            rfq_id: 0, start_after: None, limit: None 
+        }).unwrap()).unwrap();
+        assert!(quotes.quotes.is_empty());
+    }
+
+
+
+
+    fn create_otc(deps: DepsMut, seller: &str, sell: &[Coin], ask: &[Coin]) {
+        let msg = ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(ask.to_vec())),
+            ..Default::default()
+        });
+        execute(deps, mock_env(), mock_info(seller, sell), msg).unwrap();
+    }
+
+
+    #[test]
+    fn crossing_otcs_are_matched() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        create_otc(deps.as_mut(), "alice", &coins(100, "token_1"), &coins(200, "token_2"));
+        create_otc(deps.as_mut(), "bob", &coins(300, "token_2"), &coins(100, "token_1"));
+        create_otc(deps.as_mut(), "carol", &coins(100, "token_2"), &coins(100, "token_1"));
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("dave", &[]), ExecuteMsg::Match { maker_id: 0, taker_id: 2 }).unwrap_err();
+        assert!(matches!(err, ContractError::NotCrossing {}));
+
+        // price improvement stays with the taker by default
+        let res = execute(deps.as_mut(), env.clone(), mock_info("dave", &[]), ExecuteMsg::Match { maker_id: 0, taker_id: 1 }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(100, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(200, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(100, "token_2") }),
+        ));
+        assert_eq!(query_otcs(deps.as_ref(), env.clone(), None, None, None).otcs.len(), 1);
+
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::UpdateConfig { 
+            active: None, 
+            price_improvement: Some(PriceImprovement::Collector(Addr::unchecked("fees"))),
+            referral_fee: None 
+        }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateConfig { 
+            active: None, 
+            price_improvement: Some(PriceImprovement::Collector(Addr::unchecked("fees"))),
+            referral_fee: None 
+        }).unwrap();
+
+        create_otc(deps.as_mut(), "alice", &coins(100, "token_1"), &coins(200, "token_2"));
+        create_otc(deps.as_mut(), "bob", &coins(150, "token_2"), &coins(50, "token_1"));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("dave", &[]), ExecuteMsg::BatchMatch { 
+            matches: vec!(MatchPair { maker_id: 3, taker_id: 4 }) 
+        }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(50, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(100, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "fees".to_string(), amount: coins(50, "token_2") }),
+        ));
+        let fills: Vec<_> = res.events.iter().filter(|e| e.ty == "otc_filled").collect();
+        assert_eq!(fills.len(), 2);
+        assert!(fills[0].attributes.contains(&Attribute::new("otc_id", "3")));
+        assert!(fills[1].attributes.contains(&Attribute::new("otc_id", "4")));
+        assert!(res.attributes.contains(&Attribute::new("traded", "50")));
+
+        // the maker stays open with what's left
+        let otcs = query_otcs(deps.as_ref(), env, None, None, None).otcs;
+        assert_eq!(otcs.len(), 2);
+        assert_eq!(otcs[1].0, 3);
+        assert_eq!(otcs[1].1.sell_amount, Uint128::from(50u8));
+        assert_eq!(otcs[1].1.ask_amount, Uint128::from(100u8));
+    }
+
+
+
+
+    #[test]
+    fn partly_filled_taker_keeps_its_price() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        create_otc(deps.as_mut(), "alice", &coins(100, "token_1"), &coins(100, "token_2"));
+        create_otc(deps.as_mut(), "bob", &coins(300, "token_2"), &coins(200, "token_1"));
+
+        // the improvement on the filled part goes back to the taker
+        let res = execute(deps.as_mut(), env.clone(), mock_info("dave", &[]), ExecuteMsg::Match { maker_id: 0, taker_id: 1 }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(100, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(100, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(50, "token_2") }),
+        ));
+
+        let otcs = query_otcs(deps.as_ref(), env, None, None, None).otcs;
+        assert_eq!(otcs.len(), 1);
+        assert_eq!(otcs[0].0, 1);
+        assert_eq!(otcs[0].1.sell_amount, Uint128::from(150u8));
+        assert_eq!(otcs[0].1.ask_amount, Uint128::from(100u8));
+    }
+
+
+
+
+    #[test]
+    fn pair_book_is_sorted_by_price() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        create_otc(deps.as_mut(), "alice", &coins(10, "token_1"), &coins(20, "token_2"));
+        create_otc(deps.as_mut(), "bob", &coins(10, "token_1"), &coins(10, "token_2"));
+        create_otc(deps.as_mut(), "carol", &coins(5, "token_1"), &coins(10, "token_2"));
+        create_otc(deps.as_mut(), "dave", &coins(10, "token_2"), &coins(10, "token_1"));
+
+        let book: PairBookResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::PairBook { 
+            sell_asset: Denom::Native("token_1".to_string()), 
+            ask_asset: Denom::Native("token_2".to_string()), 
+            start_after: None,
+            limit: None 
+        }).unwrap()).unwrap();
+
+        let ids: Vec<u32> = book.otcs.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec!(1, 0, 2));
+
+        let page: PairBookResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::PairBook { 
+            sell_asset: Denom::Native("token_1".to_string()), 
+            ask_asset: Denom::Native("token_2".to_string()), 
+            start_after: Some((Decimal256::from_ratio(2u8, 1u8), 0)),
+            limit: None 
+        }).unwrap()).unwrap();
+
+        let ids: Vec<u32> = page.otcs.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec!(2));
+
+        assert_eq!(book.levels.len(), 2);
+        assert_eq!(book.levels[0].price, Decimal256::one());
+        assert_eq!(book.levels[1].price, Decimal256::from_ratio(2u8, 1u8));
+        assert_eq!(book.levels[1].sell_amount, Uint128::from(15u8));
+        assert_eq!(book.levels[1].ask_amount, Uint128::from(30u8));
+        assert_eq!(book.levels[1].count, 2);
+
+        let pairs: PairsResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Pairs { start_after: None, limit: None }).unwrap()).unwrap();
+        assert_eq!(pairs.pairs.len(), 2);
+        assert_eq!(pairs.pairs[0].sell_asset, Denom::Native("token_1".to_string()));
+        assert_eq!(pairs.pairs[0].count, 3);
+        assert_eq!(pairs.pairs[1].count, 1);
+
+        let page: PairsResponse = from_binary(&query(deps.as_ref(), env, QueryMsg::Pairs { 
+            start_after: Some((Denom::Native("token_1".to_string()), Denom::Native("token_2".to_string()))), 
+            limit: None 
+        }).unwrap()).unwrap();
+        assert_eq!(page.pairs.len(), 1);
+        assert_eq!(page.pairs[0].sell_asset, Denom::Native("token_2".to_string()));
+    }
+
+
+
+
+    fn best_offer(deps: Deps) -> Option<u32> {
+        let res: BestOfferResponse = from_binary(&query(deps, mock_env(), QueryMsg::BestOffer { 
+            sell_asset: Denom::Native("token_1".to_string()), 
+            ask_asset: Denom::Native("token_2".to_string()), 
+        }).unwrap()).unwrap();
+        res.offer.map(|(id, _)| id)
+    }
+
+
+    #[test]
+    fn price_index_tracks_best_offer() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+        assert_eq!(best_offer(deps.as_ref()), None);
+
+        create_otc(deps.as_mut(), "alice", &coins(10, "token_1"), &coins(20, "token_2"));
+        create_otc(deps.as_mut(), "bob", &coins(10, "token_1"), &coins(10, "token_2"));
+        create_otc(deps.as_mut(), "carol", &coins(10, "token_1"), &coins(30, "token_2"));
+        assert_eq!(best_offer(deps.as_ref()), Some(1));
+
+        let in_range: GetOTCsResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::OffersByPrice { 
+            sell_asset: Denom::Native("token_1".to_string()), 
+            ask_asset: Denom::Native("token_2".to_string()), 
+            min_price: Some(Decimal256::from_ratio(3u8, 2u8)), 
+            max_price: Some(Decimal256::from_ratio(3u8, 1u8)), 
+            limit: None 
+        }).unwrap()).unwrap();
+        let ids: Vec<u32> = in_range.otcs.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec!(0, 2));
+
+        execute(deps.as_mut(), env.clone(), mock_info("dave", &coins(10, "token_2")), ExecuteMsg::Swap { otc_id: 1, recipient: None, payout_msg: None, referrer: None }).unwrap();
+        assert_eq!(best_offer(deps.as_ref()), Some(0));
+
+        // a partial match keeps the remainder indexed at the same price
+        create_otc(deps.as_mut(), "dave", &coins(10, "token_2"), &coins(5, "token_1"));
+        execute(deps.as_mut(), env, mock_info("dave", &[]), ExecuteMsg::Match { maker_id: 0, taker_id: 3 }).unwrap();
+        assert_eq!(best_offer(deps.as_ref()), Some(0));
+    }
+
+
+
+
+    #[test]
+    fn simulate_swap_matches_execution() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+        create_otc(deps.as_mut(), "alice", &coins(5, "token_1"), &coins(10, "token_2"));
+
+        let simulate = |deps: Deps, otc_id: u32, sender: &str, amount: u128, denom: &str| -> SimulateSwapResponse {
+            from_binary(&query(deps, mock_env(), QueryMsg::SimulateSwap { 
+                otc_id, 
+                sender: sender.to_string(), 
+                offer_asset: Asset { denom: Denom::Native(denom.to_string()), amount: Uint128::from(amount) },
+                recipient: None,
+                payout_msg: None,
+                referrer: None 
+            }).unwrap()).unwrap()
+        };
+
+        let res = simulate(deps.as_ref(), 0, "alice", 10, "token_2");
+        assert_eq!(res.error, Some("Generic error: Can't swap with yourself".to_string()));
+
+        let res = simulate(deps.as_ref(), 0, "bob", 10, "token_3");
+        assert_eq!(res.error, Some(ContractError::WrongDenom {}.to_string()));
+        assert!(res.messages.is_empty());
+
+        let res = simulate(deps.as_ref(), 0, "bob", 12, "token_2");
+        assert_eq!(res.error, None);
+        assert_eq!(res.ask_amount, Uint128::from(10u8));
+        assert_eq!(res.refund_amount, Uint128::from(2u8));
+        assert_eq!(res.sell_amount, Uint128::from(5u8));
+
+        let to_carol: SimulateSwapResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::SimulateSwap { 
+            otc_id: 0, 
+            sender: "bob".to_string(), 
+            offer_asset: Asset { denom: Denom::Native("token_2".to_string()), amount: Uint128::from(12u8) },
+            recipient: Some("carol".to_string()),
+            payout_msg: None,
+            referrer: None 
+        }).unwrap()).unwrap();
+        assert!(to_carol.messages.contains(&CosmosMsg::Bank(BankMsg::Send { to_address: "carol".to_string(), amount: coins(5, "token_1") })));
+
+        let swap = |otc_id: u32| ExecuteMsg::Swap { otc_id, recipient: None, payout_msg: None, referrer: None };
+
+        let executed = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(12, "token_2")), swap(0)).unwrap();
+        let sends: Vec<_> = executed.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, res.messages);
+        assert!(res.held_messages.is_empty());
+
+        // the listing deposit comes back with the fill, escrow otcs hold the payouts in a deal
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateListingRules { 
+            deposit: Some(Coin::new(10, "uatom")), 
+            max_open_otcs: None, 
+            min_notional: vec!() 
+        }).unwrap();
+
+        for escrow in [None, Some(Escrow { arbiter: None, confirm_period: Duration::Time(3600) })] {
+            execute(deps.as_mut(), env.clone(), mock_info("alice", &[Coin::new(5, "token_1"), Coin::new(10, "uatom")]), ExecuteMsg::Create(NewOTC {
+                ask_balance: Balance::Native(NativeBalance(coins(10, "token_2"))),
+                escrow,
+                ..Default::default()
+            })).unwrap();
+        }
+
+        let res = simulate(deps.as_ref(), 1, "bob", 12, "token_2");
+        let executed = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(12, "token_2")), swap(1)).unwrap();
+        let sends: Vec<_> = executed.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, res.messages);
+        assert_eq!(sends.last(), Some(&CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(10, "uatom") })));
+
+        let res = simulate(deps.as_ref(), 2, "bob", 12, "token_2");
+        assert_eq!(res.messages, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(2, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(10, "uatom") }),
+        ));
+        let executed = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(12, "token_2")), swap(2)).unwrap();
+        let sends: Vec<_> = executed.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, res.messages);
+
+        let released = execute(deps.as_mut(), env, mock_info("bob", &[]), ExecuteMsg::ConfirmDeal { deal_id: 0 }).unwrap();
+        let sends: Vec<_> = released.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, res.held_messages);
+    }
+
+
+
+
+    #[test]
+    fn batch_create_swap_and_cancel() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        let batch = |sell: Vec<Coin>| ExecuteMsg::BatchCreate { 
+            otcs: sell.into_iter().map(|sell| BatchNewOTC {
+                sell,
+                otc: NewOTC {
+                    ask_balance: Balance::Native(NativeBalance(coins(10, "token_2"))),
+                    ..Default::default()
+                }
+            }).collect()
+        };
+
+        let funds = vec!(coins(5, "token_1")[0].clone(), coins(3, "token_3")[0].clone());
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &funds), batch(vec!(
+            coins(5, "token_1")[0].clone(), coins(2, "token_3")[0].clone()
+        ))).unwrap_err();
+        assert!(matches!(err, ContractError::FundsMismatch {}));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &funds), batch(vec!(
+            coins(2, "token_1")[0].clone(), coins(3, "token_1")[0].clone(), coins(3, "token_3")[0].clone()
+        ))).unwrap();
+        let created: BatchResponse = from_binary(&res.data.unwrap()).unwrap();
+        let ids: Vec<u32> = created.results.iter().map(|r| r.otc_id).collect();
+        assert_eq!(ids, vec!(0, 1, 2));
+        assert_eq!(created.results[1].sell_amount, Uint128::from(3u8));
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(25, "token_2")), ExecuteMsg::BatchSwap { 
+            otc_ids: vec!(0, 1, 2) 
+        }).unwrap_err();
+        assert!(matches!(err, ContractError::FundsMismatch {}));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(25, "token_2")), ExecuteMsg::BatchSwap { 
+            otc_ids: vec!(0, 1) 
+        }).unwrap();
+        let swapped: BatchResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(swapped.results.len(), 2);
+        assert_eq!(res.messages.len(), 5);
+        assert_eq!(res.messages[4].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "bob".to_string(), 
+            amount: coins(5, "token_2") 
+        }));
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), ExecuteMsg::BatchCancel { otc_ids: vec!(2) }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::BatchCancel { otc_ids: vec!(2) }).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "alice".to_string(), 
+            amount: coins(3, "token_3") 
+        }));
+        assert_eq!(query_otcs(deps.as_ref(), env, None, None, None).otcs.len(), 0);
+    }
+
+
+
+
+    #[test]
+    fn swap_pays_out_to_recipients() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        let create = ReceiveMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(10, "token_2"))),
+            recipient: Some("treasury".to_string()),
+            ..Default::default()
+        });
+        execute(deps.as_mut(), env.clone(), mock_info("cw20_token", &[]), ExecuteMsg::Receive(Cw20ReceiveMsg { 
+            sender: "alice".to_string(), 
+            amount: Uint128::from(5u8), 
+            msg: to_binary(&create).unwrap() 
+        })).unwrap();
+
+        let hook = Binary::from(b"{\"deposit\":{}}");
+
+        let res = execute(deps.as_mut(), env, mock_info("bob", &coins(10, "token_2")), ExecuteMsg::Swap { 
+            otc_id: 0, 
+            recipient: Some("vault".to_string()), 
+            payout_msg: Some(hook.clone()), 
+            referrer: None 
+        }).unwrap();
+
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "treasury".to_string(), 
+            amount: coins(10, "token_2") 
+        }));
+        assert_eq!(res.messages[1].msg, CosmosMsg::Wasm(WasmMsg::Execute { 
+            contract_addr: "cw20_token".to_string(), 
+            msg: to_binary(&Cw20ExecuteMsg::Send { 
+                contract: "vault".to_string(), 
+                amount: Uint128::from(5u8), 
+                msg: hook 
+            }).unwrap(), 
+            funds: vec!() 
+        }));
+    }
+
+
+
+
+    #[test]
+    fn hooks_are_notified_without_blocking() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        let add = ExecuteMsg::AddHook { addr: "analytics".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), add.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), add).unwrap();
+
+        let hooks: HooksResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Hooks {}).unwrap()).unwrap();
+        assert_eq!(hooks.hooks, vec!("analytics".to_string()));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "token_1")), ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(10, "token_2"))),
+            ..Default::default()
+        })).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].reply_on, ReplyOn::Error);
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(10, "token_2")), ExecuteMsg::Swap { 
+            otc_id: 0, 
+            recipient: None, 
+            payout_msg: None, 
+            referrer: None 
+        }).unwrap();
+
+        let filled = OtcHookExecuteMsg::OtcHook(OtcHookMsg::Filled { 
+            otc_id: 0, 
+            buyer: "bob".to_string(), 
+            sell_amount: Uint128::from(5u8), 
+            ask_amount: Uint128::from(10u8) 
+        });
+        assert_eq!(res.messages[2].msg, CosmosMsg::Wasm(WasmMsg::Execute { 
+            contract_addr: "analytics".to_string(), 
+            msg: to_binary(&filled).unwrap(), 
+            funds: vec!() 
+        }));
+
+        // a failing hook doesn't revert the swap
+        let res = reply(deps.as_mut(), env.clone(), Reply { 
+            id: res.messages[2].id, 
+            result: SubMsgResult::Err("out of gas".to_string()) 
+        }).unwrap();
+        assert!(res.attributes.iter().any(|attr| attr.value == "hook_failed"));
+
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::RemoveHook { addr: "analytics".to_string() }).unwrap();
+        let hooks: HooksResponse = from_binary(&query(deps.as_ref(), env, QueryMsg::Hooks {}).unwrap()).unwrap();
+        assert!(hooks.hooks.is_empty());
+    }
+
+
+
+
+    #[test]
+    fn lifecycle_events_are_emitted() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        let expires = Expiration::AtHeight(env.block.height + 10);
+        let create = ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(10, "token_2"))),
+            expires: Some(expires),
+            ..Default::default()
+        });
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "token_1")), create.clone()).unwrap();
+        assert_eq!(res.events, vec!(Event::new("otc_created")
+            .add_attribute("otc_id", "0")
+            .add_attribute("seller", "alice")
+            .add_attribute("sell_asset", "native:token_1")
+            .add_attribute("sell_amount", "5")
+            .add_attribute("ask_asset", "native:token_2")
+            .add_attribute("ask_amount", "10")
+            .add_attribute("expires", expires.to_string())
+        ));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(12, "token_2")), ExecuteMsg::Swap { 
+            otc_id: 0, 
+            recipient: None, 
+            payout_msg: None, 
+            referrer: None 
+        }).unwrap();
+        assert_eq!(res.events, vec!(Event::new("otc_filled")
+            .add_attribute("otc_id", "0")
+            .add_attribute("seller", "alice")
+            .add_attribute("buyer", "bob")
+            .add_attribute("sell_asset", "native:token_1")
+            .add_attribute("sell_amount", "5")
+            .add_attribute("ask_asset", "native:token_2")
+            .add_attribute("ask_amount", "10")
+            .add_attribute("fee", "0")
+            .add_attribute("buyer_fee", "0")
+        ));
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "token_1")), create.clone()).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "token_1")), create).unwrap();
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::Cancel { otc_id: 1 }).unwrap();
+        assert_eq!(res.events[0].ty, "otc_cancelled");
+        assert_eq!(res.events[0].attributes[0].value, "1");
+
+        env.block.height += 10;
+        let res = execute(deps.as_mut(), env, mock_info("alice", &[]), ExecuteMsg::Cancel { otc_id: 2 }).unwrap();
+        assert_eq!(res.events, vec!(Event::new("otc_expired")
+            .add_attribute("otc_id", "2")
+            .add_attribute("seller", "alice")
+            .add_attribute("sell_asset", "native:token_1")
+            .add_attribute("sell_amount", "5")
+        ));
+    }
+
+
+
+
+    #[test]
+    fn referrers_are_paid_on_both_sides() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateConfig { 
+            active: None, 
+            price_improvement: None, 
+            referral_fee: Some(Decimal::percent(10)) 
+        }).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidReferralFee { .. }));
+
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateConfig { 
+            active: None, 
+            price_improvement: None, 
+            referral_fee: Some(Decimal::percent(1)) 
+        }).unwrap();
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1000, "token_1")), ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(500, "token_2"))),
+            referrer: Some("maker_frontend".to_string()),
+            ..Default::default()
+        })).unwrap();
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(500, "token_2")), ExecuteMsg::Swap { 
+            otc_id: 0, 
+            recipient: None, 
+            payout_msg: None, 
+            referrer: Some("broker".to_string()) 
+        }).unwrap();
+
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(495, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(990, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "maker_frontend".to_string(), amount: coins(5, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "broker".to_string(), amount: coins(10, "token_1") }),
+        ));
+
+        let fees: Vec<_> = res.events[0].attributes.iter().skip(7).map(|attr| attr.value.clone()).collect();
+        assert_eq!(fees, vec!("5", "10"));
+
+        let earnings = |deps: Deps, address: &str| -> ReferralEarningsResponse {
+            from_binary(&query(deps, mock_env(), QueryMsg::ReferralEarnings { address: address.to_string() }).unwrap()).unwrap()
+        };
+
+        assert_eq!(earnings(deps.as_ref(), "maker_frontend").earnings, vec!(
+            Asset { denom: Denom::Native("token_2".to_string()), amount: Uint128::new(5) }
+        ));
+        assert_eq!(earnings(deps.as_ref(), "broker").earnings, vec!(
+            Asset { denom: Denom::Native("token_1".to_string()), amount: Uint128::new(10) }
+        ));
+        assert!(earnings(deps.as_ref(), "bob").earnings.is_empty());
+    }
+
+
+
+
+    #[test]
+    fn proceeds_are_split_with_dust_to_first_share() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        let share = |address: &str, bps: u16| PayoutShare { address: Addr::unchecked(address), bps };
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "token_1")), ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(1001, "token_2"))),
+            payout_split: Some(vec!(share("treasury", 5000), share("ops", 4000))),
+            ..Default::default()
+        })).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidPayoutSplit { .. }));
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "token_1")), ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(1001, "token_2"))),
+            payout_split: Some(vec!(share("treasury", 5000), share("ops", 3000), share("grants", 2000))),
+            ..Default::default()
+        })).unwrap();
+
+        let res = execute(deps.as_mut(), env, mock_info("bob", &coins(1001, "token_2")), ExecuteMsg::Swap { 
+            otc_id: 0, 
+            recipient: None, 
+            payout_msg: None, 
+            referrer: None 
+        }).unwrap();
+
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "treasury".to_string(), amount: coins(501, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "ops".to_string(), amount: coins(300, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "grants".to_string(), amount: coins(200, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(5, "token_1") }),
+        ));
+    }
+
+
+
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    enum MockOracleMsg {
+        SetPrice { base: Denom, quote: Denom, price: Decimal, updated_at: Timestamp }
+    }
+
+    const MOCK_PRICES: Map<(&str, &str), OraclePriceResponse> = Map::new("prices");
+
+    fn mock_oracle_execute(deps: DepsMut, _env: Env, _info: MessageInfo, msg: MockOracleMsg) -> StdResult<Response> {
+        let MockOracleMsg::SetPrice { base, quote, price, updated_at } = msg;
+        MOCK_PRICES.save(deps.storage, (&denom_key(&base), &denom_key(&quote)), &OraclePriceResponse { price, updated_at })?;
+        Ok(Response::new())
+    }
+
+    fn mock_oracle_query(deps: Deps, _env: Env, msg: OracleQueryMsg) -> StdResult<Binary> {
+        let OracleQueryMsg::Price { base, quote } = msg;
+        to_binary(&MOCK_PRICES.load(deps.storage, (&denom_key(&base), &denom_key(&quote)))?)
+    }
+
+
+    /// App with an otc contract and a mock oracle feeding token_1 prices in token_2
+    fn oracle_app() -> (App, Addr, Addr) {
+        let mut app = App::new(|router, _, storage| {
+            for user in ["alice", "bob"] {
+                router.bank.init_balance(storage, &Addr::unchecked(user), vec!(
+                    Coin::new(10000, "token_1"), 
+                    Coin::new(10000, "token_2")
+                )).unwrap();
+            }
+        });
+
+        let oracle_code = app.store_code(Box::new(ContractWrapper::new(
+            mock_oracle_execute, 
+            |_: DepsMut, _: Env, _: MessageInfo, _: Empty| -> StdResult<Response> { Ok(Response::new()) }, 
+            mock_oracle_query
+        )));
+        let otc_code = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query).with_reply(reply)));
+
+        let oracle = app.instantiate_contract(oracle_code, Addr::unchecked("admin"), &Empty {}, &[], "oracle", None).unwrap();
+        let otc = app.instantiate_contract(otc_code, Addr::unchecked("admin"), &InstantiateMsg {}, &[], "otc", None).unwrap();
+
+        (app, otc, oracle)
+    }
+
+    /// Feeds a price last updated `age` seconds ago
+    fn set_price(app: &mut App, oracle: &Addr, price: Decimal, age: u64) {
+        let updated_at = app.block_info().time.minus_seconds(age);
+        app.execute_contract(Addr::unchecked("admin"), oracle.clone(), &MockOracleMsg::SetPrice { 
+            base: Denom::Native("token_1".to_string()), 
+            quote: Denom::Native("token_2".to_string()), 
+            price, 
+            updated_at 
+        }, &[]).unwrap();
+    }
+
+
+    #[test]
+    fn pegged_ask_follows_the_oracle() {
+        let (mut app, otc, oracle) = oracle_app();
+
+        set_price(&mut app, &oracle, Decimal::percent(200), 0);
+
+        let res = app.execute_contract(Addr::unchecked("alice"), otc.clone(), &ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(1, "token_2"))),
+            pegged_price: Some(NewPeggedPrice {
+                oracle: oracle.to_string(),
+                adjustment: Some(PriceAdjustment::Discount(Decimal::percent(2))),
+                max_staleness: 60,
+                max_deviation: Some(Decimal::percent(10)),
+            }),
+            ..Default::default()
+        }), &coins(1000, "token_1")).unwrap();
+        let created: NewOTCResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(created.otc.ask_amount, Uint128::new(1960));
+
+        // the feed moves and the ask follows it
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        set_price(&mut app, &oracle, Decimal::percent(190), 0);
+
+        let price: CurrentPriceResponse = app.wrap().query_wasm_smart(&otc, &QueryMsg::CurrentPrice { otc_id: 0 }).unwrap();
+        assert_eq!(price.ask_amount, Uint128::new(1862));
+
+        let swap = ExecuteMsg::Swap { otc_id: 0, recipient: None, payout_msg: None, referrer: None };
+        let swap_err = |app: &mut App, amount: u128| -> ContractError {
+            app.execute_contract(Addr::unchecked("bob"), otc.clone(), &swap, &coins(amount, "token_2")).unwrap_err().downcast().unwrap()
+        };
+
+        let err = swap_err(&mut app, 1861);
+        assert_eq!(err.to_string(), "Generic error: Sent amount is smaller than what being asked");
+
+        set_price(&mut app, &oracle, Decimal::percent(190), 61);
+        assert!(matches!(swap_err(&mut app, 1862), ContractError::StalePrice {}));
+
+        set_price(&mut app, &oracle, Decimal::percent(170), 0);
+        assert!(matches!(swap_err(&mut app, 1862), ContractError::PriceDeviation {}));
+
+        // the excess over the current ask is refunded
+        set_price(&mut app, &oracle, Decimal::percent(190), 0);
+        app.execute_contract(Addr::unchecked("bob"), otc.clone(), &swap, &coins(1900, "token_2")).unwrap();
+
+        assert_eq!(app.wrap().query_balance("alice", "token_2").unwrap().amount, Uint128::new(11862));
+        assert_eq!(app.wrap().query_balance("bob", "token_2").unwrap().amount, Uint128::new(8138));
+        assert_eq!(app.wrap().query_balance("bob", "token_1").unwrap().amount, Uint128::new(11000));
+    }
+
+
+
+
+    #[test]
+    fn conditional_otcs_fill_once_the_condition_holds() {
+        let (mut app, otc, oracle) = oracle_app();
+
+        set_price(&mut app, &oracle, Decimal::percent(150), 0);
+        let height = app.block_info().height;
+
+        let conditions = vec!(
+            Condition::PriceAtOrBelow(PriceCondition {
+                oracle: oracle.clone(),
+                base: Denom::Native("token_1".to_string()),
+                quote: Denom::Native("token_2".to_string()),
+                price: Decimal::percent(120),
+                max_staleness: 60,
+            }),
+            Condition::After(Scheduled::AtHeight(height + 5)),
+        );
+
+        for condition in conditions {
+            app.execute_contract(Addr::unchecked("alice"), otc.clone(), &ExecuteMsg::Create(NewOTC {
+                ask_balance: Balance::Native(NativeBalance(coins(10, "token_2"))),
+                condition: Some(condition),
+                ..Default::default()
+            }), &coins(5, "token_1")).unwrap();
+        }
+
+        let fillable = |app: &App, otc_id: u32| -> FillableResponse {
+            app.wrap().query_wasm_smart(&otc, &QueryMsg::Fillable { otc_id }).unwrap()
+        };
+
+        assert_eq!(fillable(&app, 0), FillableResponse {
+            fillable: false,
+            reason: Some("The otc condition doesn't hold: price 1.5 is above 1.2".to_string())
+        });
+        assert_eq!(fillable(&app, 1).reason, Some(
+            format!("The otc condition doesn't hold: not before scheduled height: {}", height + 5)
+        ));
+
+        let swap = |otc_id: u32| ExecuteMsg::Swap { otc_id, recipient: None, payout_msg: None, referrer: None };
+
+        for otc_id in [0, 1] {
+            let err = app.execute_contract(Addr::unchecked("bob"), otc.clone(), &swap(otc_id), &coins(10, "token_2")).unwrap_err();
+            assert!(matches!(err.downcast::<ContractError>().unwrap(), ContractError::ConditionNotMet { .. }));
+        }
+
+        app.update_block(|block| block.height += 5);
+        set_price(&mut app, &oracle, Decimal::percent(120), 0);
+
+        assert_eq!(fillable(&app, 0), FillableResponse { fillable: true, reason: None });
+        assert!(fillable(&app, 1).fillable);
+
+        app.execute_contract(Addr::unchecked("bob"), otc.clone(), &swap(0), &coins(10, "token_2")).unwrap();
+        app.execute_contract(Addr::unchecked("bob"), otc, &swap(1), &coins(10, "token_2")).unwrap();
+    }
+
+
+
+
+    #[test]
+    fn escrow_deals_release_and_resolve() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        for arbiter in [None, Some(Addr::unchecked("arbiter")), Some(Addr::unchecked("arbiter"))] {
+            execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(100, "token_1")), ExecuteMsg::Create(NewOTC {
+                ask_balance: Balance::Native(NativeBalance(coins(200, "token_2"))),
+                escrow: Some(Escrow { arbiter, confirm_period: Duration::Time(3600) }),
+                ..Default::default()
+            })).unwrap();
+        }
+
+        let swap = |otc_id: u32| ExecuteMsg::Swap { otc_id, recipient: None, payout_msg: None, referrer: None };
+
+        // the payment is held, only the excess is refunded
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(210, "token_2")), swap(0)).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.events[1].ty, "deal_opened");
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(200, "token_2")), swap(1)).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(200, "token_2")), swap(2)).unwrap();
+
+        let deal: Deal = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetDeal { deal_id: 0 }).unwrap()).unwrap();
+        assert_eq!(deal.payment.amount, Uint128::new(200));
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), ExecuteMsg::DisputeDeal { deal_id: 0 }).unwrap_err();
+        assert!(matches!(err, ContractError::NoArbiter {}));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), ExecuteMsg::ReleaseDeal { deal_id: 0 }).unwrap_err();
+        assert!(matches!(err, ContractError::DeadlineNotPassed {}));
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::DisputeDeal { deal_id: 1 }).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), ExecuteMsg::DisputeDeal { deal_id: 2 }).unwrap();
+
+        env.block.time = env.block.time.plus_seconds(3600);
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), ExecuteMsg::ReleaseDeal { deal_id: 0 }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(200, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(100, "token_1") }),
+        ));
+        assert_eq!(res.events[0].ty, "deal_released");
+
+        // the arbiter has until the end of the resolution period
+        let err = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), ExecuteMsg::ReleaseDeal { deal_id: 1 }).unwrap_err();
+        assert!(matches!(err, ContractError::DeadlineNotPassed {}));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::ResolveDeal { deal_id: 1, seller_bps: 2500 }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("arbiter", &[]), ExecuteMsg::ResolveDeal { deal_id: 1, seller_bps: 2500 }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(50, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(150, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(25, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(75, "token_1") }),
+        ));
+        assert_eq!(res.events[0].ty, "deal_resolved");
+
+        // a dispute left unresolved returns both legs
+        env.block.time = env.block.time.plus_seconds(30 * 24 * 60 * 60);
+        let res = execute(deps.as_mut(), env, mock_info("carol", &[]), ExecuteMsg::ReleaseDeal { deal_id: 2 }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(200, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(100, "token_1") }),
+        ));
+        assert_eq!(res.events[0].ty, "deal_refunded");
+    }
+
+
+
+
+    #[test]
+    fn vested_sell_side_is_claimed_over_time() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1000, "token_1")), ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(200, "token_2"))),
+            vesting: Some(VestingSchedule { cliff: Duration::Time(100), duration: Duration::Time(1000) }),
+            ..Default::default()
+        })).unwrap();
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(200, "token_2")), ExecuteMsg::Swap { 
+            otc_id: 0, 
+            recipient: None, 
+            payout_msg: None, 
+            referrer: None 
+        }).unwrap();
+
+        // proceeds go out right away, the sold asset stays
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(200, "token_2") }),
+        ));
+        assert_eq!(res.events[1].ty, "position_opened");
+
+        let position = |deps: Deps, env: Env| -> PositionResponse {
+            from_binary(&query(deps, env, QueryMsg::GetPosition { position_id: 0 }).unwrap()).unwrap()
+        };
+        let claim = ExecuteMsg::ClaimVested { position_id: 0 };
+
+        env.block.time = env.block.time.plus_seconds(99);
+        assert_eq!(position(deps.as_ref(), env.clone()).claimable, Uint128::zero());
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), claim.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+
+        env.block.time = env.block.time.plus_seconds(401);
+        assert_eq!(position(deps.as_ref(), env.clone()).claimable, Uint128::new(500));
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), claim.clone()).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(500, "token_1") }));
+
+        env.block.time = env.block.time.plus_seconds(250);
+        let positions: GetPositionsResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetPositions { 
+            owner: Some("bob".to_string()), 
+            start_after: None, 
+            limit: None 
+        }).unwrap()).unwrap();
+        assert_eq!(positions.positions[0].vested, Uint128::new(750));
+        assert_eq!(positions.positions[0].claimable, Uint128::new(250));
+
+        env.block.time = env.block.time.plus_seconds(1000);
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), claim).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(500, "token_1") }));
+        assert!(query(deps.as_ref(), env, QueryMsg::GetPosition { position_id: 0 }).is_err());
+    }
+
+
+
+
+    #[test]
+    fn claim_tokens_carry_the_claim_to_the_new_owner() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1000, "token_1")), ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(200, "token_2"))),
+            vesting: Some(VestingSchedule { cliff: Duration::Time(0), duration: Duration::Time(1000) }),
+            ..Default::default()
+        })).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(100, "token_1")), ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(200, "token_2"))),
+            escrow: Some(Escrow { arbiter: None, confirm_period: Duration::Time(3600) }),
+            ..Default::default()
+        })).unwrap();
+
+        let swap = |otc_id: u32, payout_msg: Option<Binary>| ExecuteMsg::Swap { otc_id, recipient: None, payout_msg, referrer: None };
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(200, "token_2")), swap(1, Some(Binary::from(b"{}")))).unwrap_err();
+        assert!(matches!(err, ContractError::HeldPayoutMsg {}));
+
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(200, "token_2")), swap(0, None)).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(200, "token_2")), swap(1, None)).unwrap();
+
+        let tokens: TokensResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Tokens { 
+            owner: "bob".to_string(), 
+            start_after: None, 
+            limit: None 
+        }).unwrap()).unwrap();
+        assert_eq!(tokens.tokens, vec!("deal-0".to_string(), "position-0".to_string()));
+
+        for token_id in tokens.tokens {
+            let transfer = ExecuteMsg::TransferNft { recipient: "carol".to_string(), token_id };
+            let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), transfer.clone()).unwrap_err();
+            assert!(matches!(err, ContractError::Cw721(_)));
+            execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), transfer).unwrap();
+        }
+
+        let owner: OwnerOfResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::OwnerOf { 
+            token_id: "position-0".to_string(), 
+            include_expired: None 
+        }).unwrap()).unwrap();
+        assert_eq!(owner.owner, "carol");
+
+        env.block.time = env.block.time.plus_seconds(1000);
+
+        let claim = ExecuteMsg::ClaimVested { position_id: 0 };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), claim.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), claim).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { to_address: "carol".to_string(), amount: coins(1000, "token_1") }));
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), ExecuteMsg::ConfirmDeal { deal_id: 0 }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), ExecuteMsg::ConfirmDeal { deal_id: 0 }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(200, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "carol".to_string(), amount: coins(100, "token_1") }),
+        ));
+
+        // delivered claims are burned
+        let count: NumTokensResponse = from_binary(&query(deps.as_ref(), env, QueryMsg::NumTokens {}).unwrap()).unwrap();
+        assert_eq!(count.count, 0);
+    }
+
+
+
+
+    #[test]
+    fn recurring_otc_is_refilled_until_it_runs_out() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        let create = |repeat: u32| ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+            repeat: Some(repeat),
+            ..Default::default()
+        });
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(250, "token_1")), create(3)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRepeat { .. }));
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(300, "token_1")), create(3)).unwrap();
+
+        let swap = ExecuteMsg::Swap { otc_id: 0, recipient: None, payout_msg: None, referrer: None };
+
+        for fills_left in [2, 1] {
+            let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(50, "token_2")), swap.clone()).unwrap();
+            assert_eq!(res.messages[1].msg, CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(100, "token_1") }));
+
+            let otcs = query_otcs(deps.as_ref(), env.clone(), None, None, None);
+            assert_eq!(otcs.otcs[0].1.fills_left, Some(fills_left));
+            assert_eq!(otcs.otcs[0].1.sell_amount, Uint128::new(100));
+        }
+
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(50, "token_2")), swap.clone()).unwrap();
+        assert!(query_otcs(deps.as_ref(), env.clone(), None, None, None).otcs.is_empty());
+
+        // cancelling returns what is left for every remaining fill
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(300, "token_1")), create(3)).unwrap();
+        let swap = ExecuteMsg::Swap { otc_id: 1, recipient: None, payout_msg: None, referrer: None };
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(50, "token_2")), swap).unwrap();
+
+        let res = execute(deps.as_mut(), env, mock_info("alice", &[]), ExecuteMsg::Cancel { otc_id: 1 }).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(200, "token_1") }));
+    }
+
+
+
+
+    #[test]
+    fn programs_buy_in_installments_on_crank() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        // fits, too large for an installment, too expensive
+        create_otc(deps.as_mut(), "alice", &coins(100, "token_1"), &coins(60, "token_2"));
+        create_otc(deps.as_mut(), "carol", &coins(1000, "token_1"), &coins(400, "token_2"));
+        create_otc(deps.as_mut(), "dave", &coins(100, "token_1"), &coins(300, "token_2"));
+
+        let program = NewProgram {
+            buy_asset: Denom::Native("token_1".to_string()),
+            amount: Uint128::new(100),
+            max_price: Decimal256::one(),
+            interval: Duration::Height(10),
+            installments: 2,
+            tip: Uint128::new(5),
+        };
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(200, "token_2")), ExecuteMsg::CreateProgram(program.clone())).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidProgram { .. }));
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(210, "token_2")), ExecuteMsg::CreateProgram(program)).unwrap();
+
+        let crank = ExecuteMsg::Crank { program_ids: vec!(0) };
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), crank.clone()).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(60, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(100, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "keeper".to_string(), amount: coins(5, "token_2") }),
+        ));
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), crank.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NothingDue {}));
+
+        env.block.height += 10;
+        let due: GetProgramsResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetPrograms { 
+            owner: None, 
+            due_only: Some(true), 
+            start_after: None, 
+            limit: None 
+        }).unwrap()).unwrap();
+        assert_eq!(due.programs[0].1.escrow.amount, Uint128::new(145));
+
+        // nothing fits, the installment stays due and no tip is paid
+        let err = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), crank.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NothingDue {}));
+
+        // an otc the swap would reject is skipped
+        create_otc(deps.as_mut(), "erin", &coins(100, "token_1"), &coins(50, "token_2"));
+        create_otc(deps.as_mut(), "frank", &coins(100, "token_1"), &coins(80, "token_2"));
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateDenyList { 
+            add: vec!("erin".to_string()), 
+            remove: vec!() 
+        }).unwrap();
+
+        // so is one that wouldn't settle at once
+        execute(deps.as_mut(), env.clone(), mock_info("gina", &coins(100, "token_1")), ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(70, "token_2"))),
+            escrow: Some(Escrow { arbiter: None, confirm_period: Duration::Time(3600) }),
+            ..Default::default()
+        })).unwrap();
+
+        // the last installment ends the program and refunds the rest
+        let res = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), crank).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "frank".to_string(), amount: coins(80, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(100, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "keeper".to_string(), amount: coins(5, "token_2") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(60, "token_2") }),
+        ));
+        assert_eq!(res.events.last().unwrap().ty, "program_ended");
+        assert!(query(deps.as_ref(), env, QueryMsg::GetProgram { program_id: 0 }).is_err());
+    }
+
+
+
+
+    fn gated_app() -> (App, Addr, Addr) {
+        let mut app = App::new(|router, _, storage| {
+            for user in ["alice", "bob", "carol", "dave"] {
+                router.bank.init_balance(storage, &Addr::unchecked(user), vec!(
+                    Coin::new(1000, "token_1"), 
+                    Coin::new(1000, "token_2")
+                )).unwrap();
+            }
+        });
+
+        let group_code = app.store_code(Box::new(ContractWrapper::new(
+            cw4_group::contract::execute, 
+            cw4_group::contract::instantiate, 
+            cw4_group::contract::query
+        )));
+        let otc_code = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query).with_reply(reply)));
+
+        let group = app.instantiate_contract(group_code, Addr::unchecked("admin"), &cw4_group::msg::InstantiateMsg {
+            admin: None,
+            members: vec!(
+                Member { addr: "alice".to_string(), weight: 1 },
+                Member { addr: "bob".to_string(), weight: 1 },
+                Member { addr: "carol".to_string(), weight: 5 },
+            )
+        }, &[], "group", None).unwrap();
+
+        let otc = app.instantiate_contract(otc_code, Addr::unchecked("admin"), &InstantiateMsg {}, &[], "otc", None).unwrap();
+
+        (app, otc, group)
+    }
+
+
+
+    #[test]
+    fn gated_otcs_trade_between_group_members_only() {
+        let (mut app, otc, group) = gated_app();
+
+        let gated = ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+            min_member_weight: Some(3),
+            ..Default::default()
+        });
+        // the seller doesn't have to be a member
+        app.execute_contract(Addr::unchecked("dave"), otc.clone(), &gated, &coins(100, "token_1")).unwrap();
+
+        let swap = |otc_id: u32, recipient: Option<&str>| ExecuteMsg::Swap { 
+            otc_id, 
+            recipient: recipient.map(str::to_string), 
+            payout_msg: None, 
+            referrer: None 
+        };
+
+        let err = app.execute_contract(Addr::unchecked("carol"), otc.clone(), &swap(0, None), &coins(50, "token_2")).unwrap_err();
+        assert!(matches!(err.downcast::<ContractError>().unwrap(), ContractError::NoMemberGroup {}));
+
+        let set_group = ExecuteMsg::SetMemberGroup { group: Some(group.to_string()) };
+        app.execute_contract(Addr::unchecked("alice"), otc.clone(), &set_group, &[]).unwrap_err();
+        app.execute_contract(Addr::unchecked("admin"), otc.clone(), &set_group, &[]).unwrap();
+
+        for buyer in ["bob", "alice"] {
+            let err = app.execute_contract(Addr::unchecked(buyer), otc.clone(), &swap(0, None), &coins(50, "token_2")).unwrap_err();
+            assert!(matches!(err.downcast::<ContractError>().unwrap(), ContractError::NotMember { min_weight: 3 }));
+        }
+
+        // nor can a member buy for someone who isn't
+        let err = app.execute_contract(Addr::unchecked("carol"), otc.clone(), &swap(0, Some("bob")), &coins(50, "token_2")).unwrap_err();
+        assert!(matches!(err.downcast::<ContractError>().unwrap(), ContractError::NotMember { min_weight: 3 }));
+
+        app.execute_contract(Addr::unchecked("carol"), otc.clone(), &swap(0, None), &coins(50, "token_2")).unwrap();
+
+        assert_eq!(app.wrap().query_balance("carol", "token_1").unwrap().amount, Uint128::new(1100));
+        assert_eq!(app.wrap().query_balance("dave", "token_2").unwrap().amount, Uint128::new(1050));
+
+        // the deal token of a gated fill stays with members
+        app.execute_contract(Addr::unchecked("alice"), otc.clone(), &ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+            min_member_weight: Some(3),
+            escrow: Some(Escrow { arbiter: None, confirm_period: Duration::Time(3600) }),
+            ..Default::default()
+        }), &coins(100, "token_1")).unwrap();
+        app.execute_contract(Addr::unchecked("carol"), otc.clone(), &swap(1, None), &coins(50, "token_2")).unwrap();
+
+        let transfer = ExecuteMsg::TransferNft { recipient: "bob".to_string(), token_id: "deal-0".to_string() };
+        let err = app.execute_contract(Addr::unchecked("carol"), otc, &transfer, &[]).unwrap_err();
+        assert!(matches!(err.downcast::<ContractError>().unwrap(), ContractError::NotMember { min_weight: 3 }));
+    }
+
+
+
+
+    #[test]
+    fn deny_and_allow_lists_are_enforced() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        let deny = ExecuteMsg::UpdateDenyList { add: vec!("mallory".to_string(), "bob".to_string()), remove: vec!() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), deny.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), deny).unwrap();
+
+        let create = ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+            ..Default::default()
+        });
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("mallory", &coins(100, "token_1")), create.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::AddressDenied { .. }));
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(100, "token_1")), create.clone()).unwrap();
+
+        let swap = ExecuteMsg::Swap { otc_id: 0, recipient: None, payout_msg: None, referrer: None };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(50, "token_2")), swap.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::AddressDenied { .. }));
+
+        let denied: DenyListResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::DenyList { 
+            start_after: Some("bob".to_string()), 
+            limit: None 
+        }).unwrap()).unwrap();
+        assert_eq!(denied.addresses, vec!(Addr::unchecked("mallory")));
+
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateDenyList { 
+            add: vec!(), 
+            remove: vec!("bob".to_string()) 
+        }).unwrap();
+
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateAllowList { 
+            add: vec!(Denom::Native("token_1".to_string())), 
+            remove: vec!(), 
+            enforce: Some(true) 
+        }).unwrap();
+
+        // the ask asset of the listed otc is not allowed anymore
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(50, "token_2")), swap.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::AssetNotAllowed { .. }));
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("cw20_token", &[]), ExecuteMsg::Receive(Cw20ReceiveMsg { 
+            sender: "alice".to_string(), 
+            amount: Uint128::new(100), 
+            msg: to_binary(&ReceiveMsg::Create(NewOTC {
+                ask_balance: Balance::Native(NativeBalance(coins(50, "token_1"))),
+                ..Default::default()
+            })).unwrap() 
+        })).unwrap_err();
+        assert!(matches!(err, ContractError::AssetNotAllowed { .. }));
+
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateAllowList { 
+            add: vec!(Denom::Native("token_2".to_string())), 
+            remove: vec!(), 
+            enforce: None 
+        }).unwrap();
+
+        let allowed: AllowListResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::AllowList { 
+            start_after: None, 
+            limit: Some(1) 
+        }).unwrap()).unwrap();
+        assert!(allowed.enforced);
+        assert_eq!(allowed.assets, vec!(Denom::Native("token_1".to_string())));
+
+        execute(deps.as_mut(), env, mock_info("bob", &coins(50, "token_2")), swap).unwrap();
+    }
+
+
+
+
+    #[test]
+    fn deny_list_covers_every_entry_point() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        create_otc(deps.as_mut(), "alice", &coins(100, "token_1"), &coins(100, "token_2"));
+        create_otc(deps.as_mut(), "carol", &coins(100, "token_2"), &coins(100, "token_1"));
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(100, "token_1")), ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+            escrow: Some(Escrow { arbiter: None, confirm_period: Duration::Time(3600) }),
+            ..Default::default()
+        })).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("dave", &coins(50, "token_2")), ExecuteMsg::Swap { 
+            otc_id: 2, recipient: None, payout_msg: None, referrer: None 
+        }).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(100, "token_1")), ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+            expires: Some(Expiration::AtHeight(env.block.height + 1)),
+            ..Default::default()
+        })).unwrap();
+
+        let auction = NewAuction {
+            bid_asset: Denom::Native("token_2".to_string()),
+            reserve_price: Uint128::zero(),
+            min_increment: Uint128::one(),
+            end: Expiration::AtHeight(env.block.height + 10),
+            extension: None,
+            user_info: None,
+            description: None,
+            sealed: None,
+        };
+        let rfq = ExecuteMsg::CreateRfq { 
+            want: Asset { denom: Denom::Native("token_1".to_string()), amount: Uint128::new(100) }, 
+            pay_asset: Denom::Native("token_2".to_string()), 
+            expires: None 
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(100, "token_1")), ExecuteMsg::CreateAuction(auction.clone())).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), rfq.clone()).unwrap();
+
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateDenyList { 
+            add: vec!("mallory".to_string(), "carol".to_string()), 
+            remove: vec!() 
+        }).unwrap();
+
+        let program = ExecuteMsg::CreateProgram(NewProgram {
+            buy_asset: Denom::Native("token_1".to_string()),
+            amount: Uint128::new(100),
+            max_price: Decimal256::one(),
+            interval: Duration::Height(10),
+            installments: 1,
+            tip: Uint128::zero(),
+        });
+
+        let denied = [
+            ("dave", vec!(), ExecuteMsg::Match { maker_id: 0, taker_id: 1 }),
+            ("mallory", coins(100, "token_1"), ExecuteMsg::CreateAuction(auction)),
+            ("mallory", coins(10, "token_2"), ExecuteMsg::Bid { auction_id: 0 }),
+            ("mallory", vec!(), rfq),
+            ("mallory", coins(100, "token_1"), ExecuteMsg::Quote { rfq_id: 0, pay_amount: Uint128::new(90) }),
+            ("mallory", coins(100, "token_2"), program),
+            ("dave", vec!(), ExecuteMsg::TransferNft { recipient: "mallory".to_string(), token_id: "deal-0".to_string() }),
+            ("alice", coins(100, "token_1"), ExecuteMsg::Create(NewOTC {
+                ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+                referrer: Some("mallory".to_string()),
+                ..Default::default()
+            })),
+            ("alice", coins(100, "token_1"), ExecuteMsg::Create(NewOTC {
+                ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+                payout_split: Some(vec!(
+                    PayoutShare { address: Addr::unchecked("alice"), bps: 5000 },
+                    PayoutShare { address: Addr::unchecked("mallory"), bps: 5000 },
+                )),
+                ..Default::default()
+            })),
+            ("dave", coins(100, "token_2"), ExecuteMsg::Swap { otc_id: 0, recipient: None, payout_msg: None, referrer: Some("mallory".to_string()) }),
+            ("mallory", vec!(), ExecuteMsg::Crank { program_ids: vec!(0) }),
+        ];
+
+        for (sender, funds, msg) in denied {
+            let err = execute(deps.as_mut(), env.clone(), mock_info(sender, &funds), msg).unwrap_err();
+            assert!(matches!(err, ContractError::AddressDenied { .. }), "{}", err);
+        }
+
+        // nor can a denied address collect the deposit bounty of expired otcs
+        env.block.height += 1;
+        for msg in [ExecuteMsg::CleanupExpired { limit: None }, ExecuteMsg::Cancel { otc_id: 3 }] {
+            let err = execute(deps.as_mut(), env.clone(), mock_info("mallory", &[]), msg).unwrap_err();
+            assert!(matches!(err, ContractError::AddressDenied { .. }), "{}", err);
+        }
+    }
+
+
+
+
+    #[test]
+    fn listings_take_a_deposit_and_respect_the_limits() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateListingRules { 
+            deposit: Some(Coin::new(10, "uatom")), 
+            max_open_otcs: Some(1), 
+            min_notional: vec!(Asset { denom: Denom::Native("token_1".to_string()), amount: Uint128::new(50) }) 
+        }).unwrap();
+
+        let create = |expires: Option<Expiration>| ExecuteMsg::Create(NewOTC {
+            ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+            expires,
+            ..Default::default()
+        });
+        let with_deposit = |amount: u128| vec!(Coin::new(amount, "token_1"), Coin::new(10, "uatom"));
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(100, "token_1")), create(None)).unwrap_err();
+        assert!(matches!(err, ContractError::DepositMissing { .. }));
+        // nothing is left to sell once the deposit is taken
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(10, "uatom")), create(None)).unwrap_err();
+        assert!(matches!(err, ContractError::NoFunds {}));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &with_deposit(40)), create(None)).unwrap_err();
+        assert!(matches!(err, ContractError::BelowMinNotional { .. }));
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &with_deposit(100)), create(None)).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &with_deposit(100)), create(None)).unwrap_err();
+        assert!(matches!(err, ContractError::TooManyOpenOtcs { max: 1 }));
+
+        // the seller gets the deposit back along with the escrow
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::Cancel { otc_id: 0 }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(100, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(10, "uatom") }),
+        ));
+
+        let expires = Expiration::AtHeight(env.block.height + 10);
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &with_deposit(100)), create(Some(expires))).unwrap();
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), ExecuteMsg::Cancel { otc_id: 1 }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        // someone else reclaiming the expired otc keeps the deposit
+        env.block.height += 10;
+        let res = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), ExecuteMsg::Cancel { otc_id: 1 }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(100, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "carol".to_string(), amount: coins(10, "uatom") }),
+        ));
+        assert_eq!(res.events[0].ty, "otc_expired");
+
+        // cw20 listings take the deposit from a prepaid credit
+        let receive = ExecuteMsg::Receive(Cw20ReceiveMsg { 
+            sender: "alice".to_string(), 
+            amount: Uint128::new(100), 
+            msg: to_binary(&ReceiveMsg::Create(NewOTC {
+                ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+                ..Default::default()
+            })).unwrap() 
+        });
+        let err = execute(deps.as_mut(), env.clone(), mock_info("cw20_token", &[]), receive.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::DepositMissing { .. }));
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(10, "uatom")), ExecuteMsg::TopUpCredit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("cw20_token", &[]), receive).unwrap();
+
+        let credit: ListingCreditResponse = from_binary(&query(deps.as_ref(), env, QueryMsg::ListingCredit { 
+            address: "alice".to_string() 
+        }).unwrap()).unwrap();
+        assert_eq!(credit.credit, Some(Coin::new(0, "uatom")));
+    }
+
+
+
+
+    #[test]
+    fn expired_otcs_are_cleaned_up_in_expiry_order() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+
+        instantiate_contract(deps.as_mut());
+
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), ExecuteMsg::UpdateListingRules { 
+            deposit: Some(Coin::new(10, "uatom")), 
+            max_open_otcs: None, 
+            min_notional: vec!() 
+        }).unwrap();
+
+        let expiries = [
+            Expiration::AtTime(env.block.time.plus_seconds(100)),
+            Expiration::AtHeight(env.block.height + 20),
+            Expiration::AtHeight(env.block.height + 5),
+            Expiration::Never {},
+        ];
+        for (seller, expires) in ["alice", "bob", "dave", "erin"].into_iter().zip(expiries) {
+            execute(deps.as_mut(), env.clone(), mock_info(seller, &[Coin::new(100, "token_1"), Coin::new(10, "uatom")]), ExecuteMsg::Create(NewOTC {
+                ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+                expires: Some(expires),
+                ..Default::default()
+            })).unwrap();
+        }
+
+        let expired_count = |deps: Deps, env: Env| -> u32 {
+            let res: ExpiredCountResponse = from_binary(&query(deps, env, QueryMsg::ExpiredCount {}).unwrap()).unwrap();
+            assert!(!res.capped);
+            res.count
+        };
+        assert_eq!(expired_count(deps.as_ref(), env.clone()), 0);
+
+        let cleanup = |limit| ExecuteMsg::CleanupExpired { limit };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), cleanup(None)).unwrap_err();
+        assert!(matches!(err, ContractError::NothingExpired {}));
+
+        env.block.height += 5;
+        env.block.time = env.block.time.plus_seconds(100);
+        assert_eq!(expired_count(deps.as_ref(), env.clone()), 2);
+
+        // height expiries come first, the caller keeps the deposit
+        let res = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), cleanup(Some(1))).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(sends, vec!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "dave".to_string(), amount: coins(100, "token_1") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "carol".to_string(), amount: coins(10, "uatom") }),
+        ));
+        assert!(OTCS.may_load(&deps.storage, 2).unwrap().is_none());
+        assert_eq!(expired_count(deps.as_ref(), env.clone()), 1);
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), cleanup(None)).unwrap();
+        assert_eq!(res.events.iter().filter(|e| e.ty == "otc_expired").count(), 1);
+        assert!(OTCS.may_load(&deps.storage, 0).unwrap().is_none());
+
+        env.block.height += 15;
+        let res = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), cleanup(None)).unwrap();
+        assert_eq!(res.events.iter().filter(|e| e.ty == "otc_expired").count(), 1);
+        assert_eq!(expired_count(deps.as_ref(), env.clone()), 0);
+
+        // otcs without an expiry stay open
+        assert!(OTCS.has(&deps.storage, 3));
+
+        // the count stops at the largest cleanup limit and says so
+        for _ in 0..61 {
+            execute(deps.as_mut(), env.clone(), mock_info("frank", &[Coin::new(100, "token_1"), Coin::new(10, "uatom")]), ExecuteMsg::Create(NewOTC {
+                ask_balance: Balance::Native(NativeBalance(coins(50, "token_2"))),
+                expires: Some(Expiration::AtHeight(env.block.height + 1)),
+                ..Default::default()
+            })).unwrap();
+        }
+        env.block.height += 1;
+        let res: ExpiredCountResponse = from_binary(&query(deps.as_ref(), env, QueryMsg::ExpiredCount {}).unwrap()).unwrap();
+        assert_eq!(res, ExpiredCountResponse { count: 60, capped: true });
+    }
+
+
+
+
     fn instantiate_contract(deps: DepsMut) -> Response {
         let msg = InstantiateMsg {};
         let info = mock_info("admin", &[]);
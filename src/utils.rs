@@ -1,3 +1,9 @@
+use cosmwasm_std::{to_binary, Api, BankMsg, Binary, Coin, CosmosMsg, StdResult, Uint128, WasmMsg};
+use cw20::{Balance, Cw20CoinVerified, Cw20ExecuteMsg, Denom};
+use cw_utils::NativeBalance;
+
+use crate::error::ContractError;
+
 /* use cosmwasm_std::StdResult;
 
 use crate::state::OTCInfo;
@@ -7,4 +13,94 @@ pub fn parse_otcs(item: StdResult<(u8, OTCInfo)>) -> StdResult<(u32, OTCInfo)> {
         (u32::from_le_bytes(key),
         info)
     })
-} */
\ No newline at end of file
+} */
+
+
+/// Message sending `amount` of the given asset from the contract to `recipient`
+pub fn send_asset_msg(asset: &Denom, amount: Uint128, recipient: &str) -> StdResult<CosmosMsg> {
+    Ok(match asset {
+        Denom::Native(denom) => CosmosMsg::Bank(BankMsg::Send { 
+            to_address: recipient.to_string(), 
+            amount: vec!(Coin { denom: denom.clone(), amount }) 
+        }),
+        Denom::Cw20(address) => CosmosMsg::Wasm(WasmMsg::Execute { 
+            contract_addr: address.to_string(), 
+            msg: to_binary(&Cw20ExecuteMsg::Transfer { 
+                recipient: recipient.to_string(), 
+                amount 
+            })?, 
+            funds: vec!()
+        })
+    })
+}
+
+
+/// Like `send_asset_msg` but hands the asset to a contract along with `msg` if given,
+/// through cw20 `Send` or a wasm execute carrying the native funds
+pub fn payout_msg(asset: &Denom, amount: Uint128, recipient: &str, msg: Option<Binary>) -> StdResult<CosmosMsg> {
+    let msg = match msg {
+        Some(msg) => msg,
+        None => return send_asset_msg(asset, amount, recipient)
+    };
+
+    Ok(match asset {
+        Denom::Native(denom) => CosmosMsg::Wasm(WasmMsg::Execute { 
+            contract_addr: recipient.to_string(), 
+            msg, 
+            funds: vec!(Coin { denom: denom.clone(), amount })
+        }),
+        Denom::Cw20(address) => CosmosMsg::Wasm(WasmMsg::Execute { 
+            contract_addr: address.to_string(), 
+            msg: to_binary(&Cw20ExecuteMsg::Send { 
+                contract: recipient.to_string(), 
+                amount,
+                msg
+            })?, 
+            funds: vec!()
+        })
+    })
+}
+
+
+/// Unpacks a balance that must consist of exactly one asset
+pub fn single_asset(balance: Balance) -> Result<(Denom, Uint128), ContractError> {
+    match balance {
+        Balance::Native(mut balance) => {
+            let coin = balance.0.pop().ok_or(ContractError::NoFunds {})?;
+            if !balance.0.is_empty() { return Err(ContractError::TooManyDenoms {}); }
+            if coin.amount.is_zero() { return Err(ContractError::NoFunds {}); }
+            Ok((Denom::Native(coin.denom), coin.amount))
+        },
+        Balance::Cw20(token) => {
+            if token.amount.is_zero() { return Err(ContractError::NoFunds {}); }
+            Ok((Denom::Cw20(token.address), token.amount))
+        }
+    }
+}
+
+
+/// Balance of a single asset already held by the contract
+pub fn asset_balance(asset: &Denom, amount: Uint128) -> Balance {
+    match asset {
+        Denom::Native(denom) => Balance::Native(NativeBalance(vec!(Coin { denom: denom.clone(), amount }))),
+        Denom::Cw20(address) => Balance::Cw20(Cw20CoinVerified { address: address.clone(), amount })
+    }
+}
+
+
+/// Validates the contract address of cw20 denoms
+pub fn validate_denom(api: &dyn Api, denom: Denom) -> StdResult<Denom> {
+    Ok(match denom {
+        Denom::Native(denom) => Denom::Native(denom),
+        Denom::Cw20(address) => Denom::Cw20(api.addr_validate(address.as_str())?)
+    })
+}
+
+
+/// Storage friendly representation of an asset
+pub fn denom_key(denom: &Denom) -> String {
+    match denom {
+        Denom::Native(denom) => format!("native:{}", denom),
+        Denom::Cw20(address) => format!("cw20:{}", address)
+    }
+}
@@ -0,0 +1,143 @@
+use cosmwasm_std::{Addr, BlockInfo, Deps, DepsMut, Env, Event, Order, Response, StdResult, Storage};
+use cw_storage_plus::Bound;
+use cw_utils::{Duration, Scheduled};
+
+use crate::contract::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::events::{position_opened, vested_claimed};
+use crate::msg::{GetPositionsResponse, PositionResponse};
+use crate::nft::{burn, mint, Claim};
+use crate::state::{Position, VestingSchedule, POSITIONS, STATE};
+use crate::utils::send_asset_msg;
+
+
+
+pub fn validate_vesting(schedule: &VestingSchedule) -> Result<(), ContractError> {
+
+    let invalid = |reason: &str| Err(ContractError::InvalidVesting { reason: reason.to_string() });
+
+    let (cliff, duration) = match (schedule.cliff, schedule.duration) {
+        (Duration::Height(cliff), Duration::Height(duration)) => (cliff, duration),
+        (Duration::Time(cliff), Duration::Time(duration)) => (cliff, duration),
+        _ => return invalid("cliff and duration must both be heights or both be times")
+    };
+
+    if duration == 0 { return invalid("duration must be positive"); }
+    if cliff > duration { return invalid("cliff can't be longer than the duration"); }
+
+    Ok(())
+}
+
+
+
+/// Point the schedule is measured from when filled at `block`
+pub fn vesting_start(schedule: &VestingSchedule, block: &BlockInfo) -> Scheduled {
+    match schedule.duration {
+        Duration::Height(_) => Scheduled::AtHeight(block.height),
+        Duration::Time(_) => Scheduled::AtTime(block.time)
+    }
+}
+
+
+
+/// Stores the position of a filled vesting otc and mints its token to the owner
+pub fn open_position(storage: &mut dyn Storage, position: &Position) -> StdResult<Event> {
+    let mut config = STATE.load(storage)?;
+
+    // token ids are never reused
+    let position_id = config.position_index;
+
+    POSITIONS.save(storage, position_id, position)?;
+    config.position_index += 1;
+    STATE.save(storage, &config)?;
+
+    mint(storage, Claim::Position(position_id), &position.owner)?;
+
+    Ok(position_opened(position_id, position))
+}
+
+
+
+pub fn try_claim_vested(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    position_id: u32,
+) -> Result<Response, ContractError> {
+
+    let mut position = POSITIONS.load(deps.storage, position_id)?;
+
+    if position.owner != *sender { return Err(ContractError::Unauthorized {}); }
+
+    let amount = position.claimable(&env.block);
+    if amount.is_zero() { return Err(ContractError::NothingToClaim {}); }
+
+    position.claimed += amount;
+
+    if position.claimed == position.asset.amount {
+        POSITIONS.remove(deps.storage, position_id);
+        burn(deps.storage, Claim::Position(position_id))?;
+    } else {
+        POSITIONS.save(deps.storage, position_id, &position)?;
+    }
+
+    Ok(Response::new()
+        .add_message(send_asset_msg(&position.asset.denom, amount, sender.as_str())?)
+        .add_event(vested_claimed(position_id, &position, amount))
+        .add_attribute("method", "claim_vested")
+    )
+}
+
+
+
+fn position_response(env: &Env, id: u32, position: Position) -> PositionResponse {
+    PositionResponse {
+        id,
+        vested: position.vested(&env.block),
+        claimable: position.claimable(&env.block),
+        position,
+    }
+}
+
+
+
+pub fn query_position(
+    deps: Deps,
+    env: Env,
+    position_id: u32,
+) -> StdResult<PositionResponse> {
+    let position = POSITIONS.load(deps.storage, position_id)?;
+    Ok(position_response(&env, position_id, position))
+}
+
+
+
+pub fn query_positions(
+    deps: Deps,
+    env: Env,
+    owner: Option<String>,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<GetPositionsResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let owner = owner.map(|owner| deps.api.addr_validate(&owner)).transpose()?;
+
+    let positions = POSITIONS
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending
+        )
+        .filter(|position| match (position, &owner) {
+            (Ok((_, position)), Some(owner)) => position.owner == *owner,
+            _ => true
+        })
+        .take(limit)
+        .map(|position| position.map(|(id, position)| position_response(&env, id, position)))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetPositionsResponse { positions })
+}